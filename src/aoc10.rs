@@ -1,5 +1,9 @@
+use std::cmp;
+use std::thread;
 use utils;
 
+const SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+
 pub fn knot_tying_hash_round(instructions: &str) -> Option<u32> {
     let instructions = utils::parse_numbers::<u8>(instructions, ',')?;
     let mut hash = KnotTypingHash::new(256);
@@ -8,18 +12,187 @@ pub fn knot_tying_hash_round(instructions: &str) -> Option<u32> {
 }
 
 pub fn knot_tying_hash(text: &str) -> String {
-    let dense_hash = knot_tying_hash_raw(text);
-    dense_hash.iter().fold(String::new(), |mut acc, x| {
-        acc.push_str(&format!("{:02x}", x));
-        acc
-    })
+    KnotHasher::new().hash(text.as_bytes()).unwrap().to_hex()
 }
 
 pub fn knot_tying_hash_raw(text: &str) -> Vec<u8> {
-    let mut input: Vec<u8> = text.bytes().collect();
-    input.append(&mut vec![17, 31, 73, 47, 23]);
-    let mut hash = KnotTypingHash::new(256);
-    hash.compute_dense_hash(&input)
+    fast_knot_hash(text.as_bytes()).to_vec()
+}
+
+pub fn fast_knot_hash(data: &[u8]) -> [u8; 16] {
+    let mut ring = [0u8; 256];
+    for (index, value) in ring.iter_mut().enumerate() {
+        *value = index as u8;
+    }
+    let mut position = 0u8;
+    let mut skip_size = 0u8;
+    for _ in 0..64 {
+        for &length in data.iter().chain(SUFFIX.iter()) {
+            let mut left = position;
+            let mut right = position.wrapping_add(length).wrapping_sub(1);
+            for _ in 0..(length / 2) {
+                ring.swap(left as usize, right as usize);
+                left = left.wrapping_add(1);
+                right = right.wrapping_sub(1);
+            }
+            position = position.wrapping_add(length).wrapping_add(skip_size);
+            skip_size = skip_size.wrapping_add(1);
+        }
+    }
+    let mut dense_hash = [0u8; 16];
+    for (block, value) in ring.chunks(16).zip(dense_hash.iter_mut()) {
+        *value = block.iter().fold(0, |acc, x| acc ^ x);
+    }
+    dense_hash
+}
+
+pub fn knot_hash_batch<T: AsRef<[u8]> + Sync>(keys: &[T]) -> Vec<[u8; 16]> {
+    let workers = thread::available_parallelism().map_or(1, |x| x.get());
+    let chunk_size = cmp::max(1, keys.len().div_ceil(workers));
+    let mut hashes = vec![[0u8; 16]; keys.len()];
+    thread::scope(|scope| {
+        let chunks = keys.chunks(chunk_size).zip(hashes.chunks_mut(chunk_size));
+        for (keys, hashes) in chunks {
+            scope.spawn(move || for (key, hash) in keys.iter().zip(hashes.iter_mut()) {
+                *hash = fast_knot_hash(key.as_ref());
+            });
+        }
+    });
+    hashes
+}
+
+pub fn knot_hash_hex(data: &[u8]) -> String {
+    Digest(fast_knot_hash(data).to_vec()).to_hex()
+}
+
+pub fn format_checksum(data: &[u8], name: &str) -> String {
+    format!("{}  {}", knot_hash_hex(data), name)
+}
+
+pub fn parse_checksum(line: &str) -> Option<(&str, &str)> {
+    let hash = line.get(..32)?;
+    if !hash.chars().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+    let name = line.get(32..)?;
+    let name = if name.starts_with("  ") || name.starts_with(" *") {
+        &name[2..]
+    } else {
+        return None;
+    };
+    if name.is_empty() { None } else { Some((hash, name)) }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct KnotHasher {
+    ring_size: u32,
+    rounds: u32,
+    suffix: Vec<u8>,
+    block_size: usize,
+}
+
+impl KnotHasher {
+    pub fn new() -> KnotHasher {
+        KnotHasher {
+            ring_size: 256,
+            rounds: 64,
+            suffix: SUFFIX.to_vec(),
+            block_size: 16,
+        }
+    }
+
+    pub fn ring_size(mut self, ring_size: u32) -> KnotHasher {
+        self.ring_size = ring_size;
+        self
+    }
+
+    pub fn rounds(mut self, rounds: u32) -> KnotHasher {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &[u8]) -> KnotHasher {
+        self.suffix = suffix.to_vec();
+        self
+    }
+
+    pub fn block_size(mut self, block_size: usize) -> KnotHasher {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn build(&self) -> Option<KnotHash> {
+        let ring_size = self.ring_size as usize;
+        if ring_size == 0 || ring_size > 256 || !ring_size.is_multiple_of(self.block_size) {
+            return None;
+        }
+        Some(KnotHash {
+            hasher: self.clone(),
+            input: vec![],
+        })
+    }
+
+    pub fn hash(&self, data: &[u8]) -> Option<Digest> {
+        let mut hash = self.build()?;
+        hash.update(data);
+        Some(hash.finalize())
+    }
+}
+
+impl Default for KnotHasher {
+    fn default() -> KnotHasher {
+        KnotHasher::new()
+    }
+}
+
+// Every round replays the whole input, so updates are buffered until finalize.
+#[derive(Debug, Clone)]
+pub struct KnotHash {
+    hasher: KnotHasher,
+    input: Vec<u8>,
+}
+
+impl KnotHash {
+    pub fn update(&mut self, data: &[u8]) {
+        self.input.extend_from_slice(data);
+    }
+
+    pub fn finalize(mut self) -> Digest {
+        self.input.extend_from_slice(&self.hasher.suffix);
+        let mut hash = KnotTypingHash::new(self.hasher.ring_size);
+        Digest(hash.compute_dense_hash(
+            &self.input,
+            self.hasher.rounds,
+            self.hasher.block_size,
+        ))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Digest(Vec<u8>);
+
+impl Digest {
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().fold(String::new(), |mut acc, x| {
+            acc.push_str(&format!("{:02x}", x));
+            acc
+        })
+    }
+
+    pub fn to_bits(&self) -> Vec<bool> {
+        self.0
+            .iter()
+            .flat_map(|x| (0..8).rev().map(move |bit| x & (1 << bit) != 0))
+            .collect()
+    }
 }
 
 struct KnotTypingHash {
@@ -37,7 +210,7 @@ impl KnotTypingHash {
         }
     }
 
-    fn compute(&mut self, instructions: &Vec<u8>) {
+    fn compute(&mut self, instructions: &[u8]) {
         let string_length = self.hash.len();
         for instruction in instructions.iter() {
             let instruction = *instruction as usize;
@@ -51,16 +224,14 @@ impl KnotTypingHash {
             self.skip_size = (self.skip_size + 1) % string_length;
         }
     }
-    fn compute_dense_hash(&mut self, instructions: &Vec<u8>) -> Vec<u8> {
-        for _ in 0..64 {
-            self.compute(&instructions);
-        }
-        let mut dense_hash = vec![];
-        for i in 0..16 {
-            let slice = &self.hash[(i * 16)..((i + 1) * 16)];
-            dense_hash.push(slice.iter().fold(0, |acc, x| acc ^ (*x as u8)));
+    fn compute_dense_hash(&mut self, instructions: &[u8], rounds: u32, block_size: usize) -> Vec<u8> {
+        for _ in 0..rounds {
+            self.compute(instructions);
         }
-        dense_hash
+        self.hash
+            .chunks(block_size)
+            .map(|x| x.iter().fold(0, |acc, x| acc ^ (*x as u8)))
+            .collect()
     }
 }
 
@@ -87,3 +258,75 @@ fn test_examples() {
         knot_tying_hash("1,2,3")
     );
 }
+
+#[test]
+fn test_knot_hasher() {
+    let mut hash = KnotHasher::new().build().unwrap();
+    hash.update(b"AoC ");
+    hash.update(b"2017");
+    let digest = hash.finalize();
+    assert_eq!("33efeb34ea91902bb2f59c9920caa6cd", digest.to_hex());
+    assert_eq!(16, digest.bytes().len());
+    assert_eq!(
+        vec![false, false, true, true, false, false, true, true],
+        digest.to_bits()[..8].to_vec()
+    );
+    let digest = KnotHasher::new()
+        .ring_size(5)
+        .rounds(1)
+        .suffix(&[])
+        .block_size(1)
+        .hash(&[3, 4, 1, 5])
+        .unwrap();
+    assert_eq!(vec![3, 4, 2, 1, 0], digest.into_bytes());
+    assert_eq!(
+        4,
+        KnotHasher::new().block_size(64).hash(b"").unwrap().bytes().len()
+    );
+    assert!(KnotHasher::new().block_size(0).build().is_none());
+    assert!(KnotHasher::new().ring_size(0).hash(b"").is_none());
+    assert!(KnotHasher::new().block_size(100).build().is_none());
+    assert!(KnotHasher::new().ring_size(512).build().is_none());
+}
+
+#[test]
+fn test_fast_knot_hash() {
+    for text in ["", "AoC 2017", "1,2,3", "flqrgnkx-127"].iter() {
+        assert_eq!(
+            KnotHasher::new().hash(text.as_bytes()).unwrap().bytes(),
+            &fast_knot_hash(text.as_bytes())[..]
+        );
+    }
+    let long = (0..1000).map(|x| (x % 256) as u8).collect::<Vec<u8>>();
+    assert_eq!(
+        KnotHasher::new().hash(&long).unwrap().bytes(),
+        &fast_knot_hash(&long)[..]
+    );
+    let keys = (0..50).map(|x| format!("key-{}", x)).collect::<Vec<String>>();
+    let hashes = knot_hash_batch(&keys);
+    assert_eq!(50, hashes.len());
+    for (key, hash) in keys.iter().zip(hashes.iter()) {
+        assert_eq!(fast_knot_hash(key.as_bytes()), *hash);
+    }
+    assert!(knot_hash_batch::<&[u8]>(&[]).is_empty());
+}
+
+#[test]
+fn test_checksum() {
+    assert_eq!(
+        "33efeb34ea91902bb2f59c9920caa6cd  file.txt",
+        format_checksum(b"AoC 2017", "file.txt")
+    );
+    assert_eq!(
+        Some(("33efeb34ea91902bb2f59c9920caa6cd", "a b.txt")),
+        parse_checksum("33efeb34ea91902bb2f59c9920caa6cd  a b.txt")
+    );
+    assert_eq!(
+        Some(("33efeb34ea91902bb2f59c9920caa6cd", "-")),
+        parse_checksum("33efeb34ea91902bb2f59c9920caa6cd *-")
+    );
+    assert_eq!(None, parse_checksum("33efeb34ea91902bb2f59c9920caa6cd file"));
+    assert_eq!(None, parse_checksum("33efeb34ea91902bb2f59c9920caa6cd  "));
+    assert_eq!(None, parse_checksum("x3efeb34ea91902bb2f59c9920caa6cd  file"));
+    assert_eq!(None, parse_checksum("33ef  file"));
+}
@@ -10,7 +10,7 @@ pub fn find_shortest_path(path: &str) -> Option<u32> {
     Some(
         directions
             .iter()
-            .fold(Hex::new(), |acc, x| acc.step(&x))
+            .fold(Hex::new(), |acc, x| acc.step(x))
             .distance_from_start(),
     )
 }
@@ -22,7 +22,7 @@ pub fn find_furthest_point(path: &str) -> Option<u32> {
         .ok()?;
     let mut max = 0;
     directions.iter().fold(Hex::new(), |acc, x| {
-        let position = acc.step(&x);
+        let position = acc.step(x);
         let distance = position.distance_from_start();
         if distance > max {
             max = distance;
@@ -57,7 +57,7 @@ impl Direction {
 impl FromStr for Direction {
     type Err = utils::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.as_ref() {
+        match s {
             "n" => Ok(Direction::North),
             "ne" => Ok(Direction::NorthEast),
             "se" => Ok(Direction::SouthEast),
@@ -1,27 +1,123 @@
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::collections::LinkedList;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
 use utils;
 
 pub fn connected_programs(pipe_network: &str) -> Option<u32> {
-    let pipes = parse_pipe_networkpipe_network(pipe_network)?;
-    Some(pipes.count_connected(0))
+    let mut groups = parse_groups(pipe_network)?;
+    Some(groups.group_size(0)? as u32)
 }
 
 pub fn program_groups(pipe_network: &str) -> Option<u32> {
-    let pipes = parse_pipe_networkpipe_network(pipe_network)?;
-    Some(pipes.group_count())
+    let groups = parse_groups(pipe_network)?;
+    Some(groups.group_count() as u32)
 }
 
-fn parse_pipe_networkpipe_network(pipe_network: &str) -> Option<Pipes> {
-    let mut pipes = Pipes::new();
+pub fn parse_groups(pipe_network: &str) -> Option<DisjointSet> {
+    let mut groups = DisjointSet::new();
     for line in pipe_network.lines() {
-        let (source, destinations) = parse_pipe(&line)?;
-        destinations.iter().for_each(|x| pipes.add_pipe(source, *x));
+        groups.add_pipe(line)?;
+    }
+    Some(groups)
+}
+
+pub fn parse_pipe_network(pipe_network: &str) -> Option<Graph> {
+    let mut pipes = Graph::new();
+    for line in pipe_network.lines() {
+        let (source, destinations) = parse_pipe(line)?;
+        pipes.add_node(source);
+        destinations.iter().for_each(|x| pipes.add_edge(source, *x));
     }
     Some(pipes)
 }
 
+pub fn parse_pipe_network_checked(pipe_network: &str, repair: bool) -> (Graph, Vec<Diagnostic>) {
+    let mut diagnostics = vec![];
+    let mut pipes = vec![];
+    let mut declared: HashMap<u32, HashSet<u32>> = HashMap::new();
+    for (index, line) in pipe_network.lines().enumerate() {
+        match parse_pipe(line) {
+            Some((source, destinations)) => {
+                declared.entry(source).or_default().extend(
+                    destinations.iter().cloned(),
+                );
+                pipes.push((index + 1, source, destinations));
+            }
+            None => diagnostics.push(Diagnostic::InvalidLine(index + 1)),
+        }
+    }
+    // Without repair every pipe is kept as declared; with it, only pipes both
+    // described programs agree on survive.
+    let mut graph = Graph::new();
+    for (line, source, destinations) in pipes {
+        graph.add_node(source);
+        for destination in destinations {
+            let diagnostic = if destination == source {
+                Some(Diagnostic::SelfLoop(line, source))
+            } else {
+                match declared.get(&destination) {
+                    None => Some(Diagnostic::UnknownProgram(line, destination)),
+                    Some(back) if !back.contains(&source) => {
+                        Some(Diagnostic::Asymmetric(line, source, destination))
+                    }
+                    Some(_) => None,
+                }
+            };
+            match diagnostic {
+                Some(diagnostic) => {
+                    diagnostics.push(diagnostic);
+                    if !repair {
+                        graph.add_edge(source, destination);
+                    }
+                }
+                None => graph.add_edge(source, destination),
+            }
+        }
+    }
+    diagnostics.sort_by_key(|x| x.line());
+    (graph, diagnostics)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Diagnostic {
+    InvalidLine(usize),
+    UnknownProgram(usize, u32),
+    Asymmetric(usize, u32, u32),
+    SelfLoop(usize, u32),
+}
+
+impl Diagnostic {
+    pub fn line(&self) -> usize {
+        match *self {
+            Diagnostic::InvalidLine(line) |
+            Diagnostic::UnknownProgram(line, _) |
+            Diagnostic::Asymmetric(line, _, _) |
+            Diagnostic::SelfLoop(line, _) => line,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Diagnostic::InvalidLine(line) => write!(f, "line {}: invalid pipe description", line),
+            Diagnostic::UnknownProgram(line, program) => {
+                write!(f, "line {}: program {} is never described", line, program)
+            }
+            Diagnostic::Asymmetric(line, source, destination) => write!(
+                f,
+                "line {}: program {} does not list {} back",
+                line,
+                destination,
+                source
+            ),
+            Diagnostic::SelfLoop(line, program) => {
+                write!(f, "line {}: program {} is connected to itself", line, program)
+            }
+        }
+    }
+}
+
 fn parse_pipe(pipe_description: &str) -> Option<(u32, Vec<u32>)> {
     let mut tokens = pipe_description.split("<->");
     let source = tokens.next()?.trim().parse().ok()?;
@@ -29,51 +125,301 @@ fn parse_pipe(pipe_description: &str) -> Option<(u32, Vec<u32>)> {
     Some((source, destinations))
 }
 
-struct Pipes {
-    pipes: HashMap<u32, Vec<u32>>,
+#[derive(Debug, Clone, Default)]
+pub struct DisjointSet {
+    indices: HashMap<u32, usize>,
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+    group_count: usize,
 }
 
-impl Pipes {
-    fn new() -> Pipes {
-        Pipes { pipes: HashMap::new() }
+impl DisjointSet {
+    pub fn new() -> DisjointSet {
+        DisjointSet {
+            indices: HashMap::new(),
+            parents: vec![],
+            sizes: vec![],
+            group_count: 0,
+        }
     }
 
-    fn add_pipe(&mut self, source: u32, destination: u32) {
-        self.pipes.entry(source).or_insert(vec![]).push(destination);
-        self.pipes.entry(destination).or_insert(vec![]).push(source);
+    pub fn add_pipe(&mut self, pipe_description: &str) -> Option<()> {
+        let (source, destinations) = parse_pipe(pipe_description)?;
+        self.add_node(source);
+        destinations.iter().for_each(|x| self.union(source, *x));
+        Some(())
     }
 
-    fn count_connected(&self, node: u32) -> u32 {
-        self.find_group(node).len() as u32
+    pub fn add_node(&mut self, node: u32) -> usize {
+        let parents = &mut self.parents;
+        let sizes = &mut self.sizes;
+        let group_count = &mut self.group_count;
+        *self.indices.entry(node).or_insert_with(|| {
+            parents.push(parents.len());
+            sizes.push(1);
+            *group_count += 1;
+            parents.len() - 1
+        })
     }
 
-    fn find_group(&self, node: u32) -> HashSet<u32> {
-        let mut visited: HashSet<u32> = HashSet::new();
-        let mut queue: LinkedList<u32> = LinkedList::new();
+    pub fn union(&mut self, left: u32, right: u32) {
+        let left = self.add_node(left);
+        let right = self.add_node(right);
+        let left = self.find(left);
+        let right = self.find(right);
+        if left == right {
+            return;
+        }
+        let (large, small) = if self.sizes[left] >= self.sizes[right] {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        self.parents[small] = large;
+        self.sizes[large] += self.sizes[small];
+        self.group_count -= 1;
+    }
+
+    pub fn connected(&mut self, left: u32, right: u32) -> bool {
+        match (self.indices.get(&left), self.indices.get(&right)) {
+            (Some(&left), Some(&right)) => self.find(left) == self.find(right),
+            _ => false,
+        }
+    }
+
+    pub fn group_size(&mut self, node: u32) -> Option<usize> {
+        let index = *self.indices.get(&node)?;
+        let root = self.find(index);
+        Some(self.sizes[root])
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.group_count
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut index = index;
+        while self.parents[index] != root {
+            let next = self.parents[index];
+            self.parents[index] = root;
+            index = next;
+        }
+        root
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DegreeStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Graph {
+    edges: BTreeMap<u32, BTreeSet<u32>>,
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        Graph { edges: BTreeMap::new() }
+    }
+
+    pub fn add_node(&mut self, node: u32) {
+        self.edges.entry(node).or_default();
+    }
+
+    pub fn add_edge(&mut self, source: u32, destination: u32) {
+        self.edges.entry(source).or_default().insert(destination);
+        self.edges.entry(destination).or_default().insert(source);
+    }
+
+    pub fn nodes(&self) -> Vec<u32> {
+        self.edges.keys().cloned().collect()
+    }
+
+    pub fn neighbours(&self, node: u32) -> Option<Vec<u32>> {
+        Some(self.edges.get(&node)?.iter().cloned().collect())
+    }
+
+    pub fn degree(&self, node: u32) -> Option<usize> {
+        Some(self.edges.get(&node)?.len())
+    }
+
+    pub fn degree_stats(&self) -> Option<DegreeStats> {
+        let degrees = self.edges.values().map(|x| x.len()).collect::<Vec<usize>>();
+        Some(DegreeStats {
+            min: *degrees.iter().min()?,
+            max: *degrees.iter().max()?,
+            mean: degrees.iter().sum::<usize>() as f64 / degrees.len() as f64,
+        })
+    }
+
+    pub fn component(&self, node: u32) -> Option<Vec<u32>> {
+        let mut component = self.distances(node)?.keys().cloned().collect::<Vec<u32>>();
+        component.sort();
+        Some(component)
+    }
+
+    pub fn components(&self) -> Vec<Vec<u32>> {
+        let labels = self.component_labels();
+        let mut components = vec![vec![]; labels.values().map(|x| x + 1).max().unwrap_or(0)];
+        for node in self.edges.keys() {
+            components[labels[node]].push(*node);
+        }
+        components
+    }
+
+    pub fn component_labels(&self) -> HashMap<u32, usize> {
+        let mut labels = HashMap::new();
+        let mut label = 0;
+        for node in self.edges.keys() {
+            if labels.contains_key(node) {
+                continue;
+            }
+            for member in self.distances(*node).unwrap().keys() {
+                labels.insert(*member, label);
+            }
+            label += 1;
+        }
+        labels
+    }
+
+    pub fn shortest_path(&self, from: u32, to: u32) -> Option<Vec<u32>> {
+        if !self.edges.contains_key(&to) {
+            return None;
+        }
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::new();
+        previous.insert(from, from);
+        queue.push_back(from);
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(previous[path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.edges.get(&node)?.iter() {
+                if !previous.contains_key(next) {
+                    previous.insert(*next, node);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn diameter(&self) -> Option<u32> {
+        self.edges
+            .keys()
+            .map(|x| *self.distances(*x).unwrap().values().max().unwrap())
+            .max()
+    }
+
+    pub fn bridges(&self) -> Vec<(u32, u32)> {
+        self.low_links().0
+    }
+
+    pub fn articulation_points(&self) -> Vec<u32> {
+        self.low_links().1
+    }
+
+    fn distances(&self, node: u32) -> Option<HashMap<u32, u32>> {
+        self.edges.get(&node)?;
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(node, 0);
         queue.push_back(node);
-        while queue.len() > 0 {
-            let node = queue.pop_front().unwrap();
-            if !visited.contains(&node) {
-                visited.insert(node);
-                for child in self.pipes.get(&node).unwrap() {
-                    if !visited.contains(child) {
-                        queue.push_back(*child);
-                    }
+        while let Some(node) = queue.pop_front() {
+            let distance = distances[&node] + 1;
+            for next in self.edges[&node].iter() {
+                if !distances.contains_key(next) {
+                    distances.insert(*next, distance);
+                    queue.push_back(*next);
                 }
             }
         }
-        visited
+        Some(distances)
     }
 
-    fn group_count(&self) -> u32 {
-        let mut groups = 0;
-        let mut nodes: HashSet<&u32> = self.pipes.keys().collect();
-        while !nodes.is_empty() {
-            let visited = self.find_group(**nodes.iter().next().unwrap());
-            nodes.retain(|x| !visited.contains(x));
-            groups += 1;
+    fn low_links(&self) -> (Vec<(u32, u32)>, Vec<u32>) {
+        let nodes = self.nodes();
+        let indices = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (*node, index))
+            .collect::<HashMap<u32, usize>>();
+        let edges = nodes
+            .iter()
+            .map(|x| self.edges[x].iter().map(|y| indices[y]).collect())
+            .collect::<Vec<Vec<usize>>>();
+        let mut discovered: Vec<Option<usize>> = vec![None; nodes.len()];
+        let mut low = vec![0; nodes.len()];
+        let mut parent: Vec<Option<usize>> = vec![None; nodes.len()];
+        let mut articulation = vec![false; nodes.len()];
+        let mut bridges = vec![];
+        let mut time = 0;
+        for root in 0..nodes.len() {
+            if discovered[root].is_some() {
+                continue;
+            }
+            let mut root_children = 0;
+            discovered[root] = Some(time);
+            low[root] = time;
+            time += 1;
+            let mut stack = vec![(root, 0)];
+            while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+                if let Some(&child) = edges[node].get(*next) {
+                    *next += 1;
+                    match discovered[child] {
+                        Some(_) if child == node || Some(child) == parent[node] => {}
+                        Some(child_time) => low[node] = cmp::min(low[node], child_time),
+                        None => {
+                            discovered[child] = Some(time);
+                            low[child] = time;
+                            time += 1;
+                            parent[child] = Some(node);
+                            if node == root {
+                                root_children += 1;
+                            }
+                            stack.push((child, 0));
+                        }
+                    }
+                    continue;
+                }
+                stack.pop();
+                if let Some(parent) = parent[node] {
+                    low[parent] = cmp::min(low[parent], low[node]);
+                    let parent_time = discovered[parent].unwrap();
+                    if low[node] > parent_time {
+                        bridges.push((nodes[parent], nodes[node]));
+                    }
+                    if parent != root && low[node] >= parent_time {
+                        articulation[parent] = true;
+                    }
+                }
+            }
+            articulation[root] = root_children > 1;
         }
-        groups
+        let mut bridges = bridges
+            .into_iter()
+            .map(|(x, y)| (cmp::min(x, y), cmp::max(x, y)))
+            .collect::<Vec<(u32, u32)>>();
+        bridges.sort();
+        let articulation = nodes
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| articulation[index])
+            .map(|(_, node)| *node)
+            .collect();
+        (bridges, articulation)
     }
 }
 
@@ -93,6 +439,97 @@ fn test_examples() {
     );
 }
 
+#[test]
+fn test_graph() {
+    let graph = parse_pipe_network(
+        "0 <-> 2\n1 <-> 1\n2 <-> 0, 3, 4\n3 <-> 2, 4\n4 <-> 2, 3, 6\n5 <-> 6\n6 <-> 4, 5",
+    ).unwrap();
+    assert_eq!(
+        vec![vec![0, 2, 3, 4, 5, 6], vec![1]],
+        graph.components()
+    );
+    assert_eq!(Some(&1), graph.component_labels().get(&1));
+    assert_eq!(Some(vec![0, 2, 4, 6, 5]), graph.shortest_path(0, 5));
+    assert_eq!(Some(vec![3]), graph.shortest_path(3, 3));
+    assert_eq!(None, graph.shortest_path(0, 1));
+    assert_eq!(None, graph.shortest_path(0, 9));
+    assert_eq!(Some(4), graph.diameter());
+    assert_eq!(vec![(0, 2), (4, 6), (5, 6)], graph.bridges());
+    assert_eq!(vec![2, 4, 6], graph.articulation_points());
+    assert_eq!(Some(3), graph.degree(2));
+    assert_eq!(
+        Some(DegreeStats {
+            min: 1,
+            max: 3,
+            mean: 13.0 / 7.0,
+        }),
+        graph.degree_stats()
+    );
+    assert_eq!(None, Graph::new().diameter());
+    assert_eq!(None, connected_programs("1 <-> 2"));
+}
+
+#[test]
+fn test_disjoint_set() {
+    let mut groups = DisjointSet::new();
+    for line in "0 <-> 2\n1 <-> 1\n2 <-> 0, 3, 4\n3 <-> 2, 4".lines() {
+        groups.add_pipe(line).unwrap();
+    }
+    assert_eq!(2, groups.group_count());
+    assert!(groups.connected(0, 4));
+    assert!(!groups.connected(0, 1));
+    assert!(!groups.connected(0, 9));
+    assert_eq!(Some(4), groups.group_size(3));
+    assert_eq!(None, groups.group_size(9));
+    assert_eq!(None, groups.add_pipe("5 <- 6"));
+    groups.add_pipe("4 <-> 2, 3, 6").unwrap();
+    groups.add_pipe("5 <-> 6").unwrap();
+    assert_eq!(Some(6), groups.group_size(0));
+    assert_eq!(2, groups.group_count());
+    groups.union(1, 5);
+    assert_eq!(1, groups.group_count());
+    assert_eq!(Some(7), groups.group_size(1));
+}
+
+#[test]
+fn test_parse_pipe_network_checked() {
+    let network = "0 <-> 2, 3\n1 <-> 1\n2 <-> 0, 5\n3 <-> 2\nfoo";
+    let (graph, diagnostics) = parse_pipe_network_checked(network, false);
+    assert_eq!(
+        vec![
+            Diagnostic::Asymmetric(1, 0, 3),
+            Diagnostic::SelfLoop(2, 1),
+            Diagnostic::UnknownProgram(3, 5),
+            Diagnostic::Asymmetric(4, 3, 2),
+            Diagnostic::InvalidLine(5),
+        ],
+        diagnostics
+    );
+    assert_eq!(vec![vec![0, 2, 3, 5], vec![1]], graph.components());
+    assert_eq!(Some(vec![1]), graph.neighbours(1));
+    assert_eq!(Some(vec![2]), graph.neighbours(5));
+    assert_eq!(Some(vec![0, 2]), graph.neighbours(3));
+    assert_eq!(
+        "line 4: program 2 does not list 3 back",
+        format!("{}", diagnostics[3])
+    );
+    let (repaired, repaired_diagnostics) = parse_pipe_network_checked(network, true);
+    assert_eq!(diagnostics, repaired_diagnostics);
+    assert_eq!(vec![vec![0, 2], vec![1], vec![3]], repaired.components());
+    assert_eq!(Some(vec![]), repaired.neighbours(1));
+    assert_eq!(None, repaired.neighbours(5));
+    assert_eq!(Some(vec![]), repaired.neighbours(3));
+    assert_eq!(Some(vec![2]), repaired.neighbours(0));
+
+    let network = "0 <-> 1\n1 <-> 2\n2 <-> 1";
+    let (graph, diagnostics) = parse_pipe_network_checked(network, false);
+    assert_eq!(vec![Diagnostic::Asymmetric(1, 0, 1)], diagnostics);
+    assert_eq!(parse_pipe_network(network).unwrap(), graph);
+    assert_eq!(vec![vec![0, 1, 2]], graph.components());
+    let (graph, _) = parse_pipe_network_checked(network, true);
+    assert_eq!(vec![vec![0], vec![1, 2]], graph.components());
+}
+
 #[test]
 fn test_parse_pipe() {
     assert_eq!(Some((0, vec![2])), parse_pipe("0 <-> 2"));
@@ -38,7 +38,7 @@ fn parse_scanner(scanner: &str) -> Option<HashMap<u32, u32>> {
 fn is_caught(distance: u32, depth: u32, delay: u32) -> bool {
     match depth {
         1 => true,
-        x => (distance + delay) % (2 * (x - 1)) == 0,
+        x => (distance + delay).is_multiple_of(2 * (x - 1)),
     }
 }
 
@@ -1,56 +1,345 @@
 use aoc10;
+use std::cmp;
+use std::collections::BTreeSet;
 
 pub fn count_used_squares(input: &str) -> u32 {
-    create_memory_map(input).iter().fold(0, |acc, x| {
-        acc + x.iter().fold(0, |acc, y| acc + y.count_ones())
-    })
+    Grid::from_key(input, 128).count_used() as u32
 }
 
 pub fn count_regions(input: &str) -> u32 {
-    let mut memory: Vec<Vec<char>> = create_memory_map(input)
-        .iter()
-        .map(|x| {
-            x.iter()
-                .map(|y| format!("{:08b}", y))
-                .fold(String::new(), |acc, z| acc + &z)
-                .chars()
-                .collect()
-        })
-        .collect();
-    let mut region_count = 0;
-    for x in 0..128 {
-        for y in 0..128 {
-            if memory[x][y] == '1' {
-                region_count += 1;
-                remove_neighbours(&mut memory, x, y);
+    count_regions_with(input, Connectivity::Four)
+}
+
+pub fn count_regions_with(input: &str, connectivity: Connectivity) -> u32 {
+    Grid::from_key(input, 128).regions_with(connectivity).count() as u32
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        match *self {
+            Connectivity::Four => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    bits: Vec<u64>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Grid {
+        let words_per_row = width.div_ceil(64);
+        Grid {
+            width,
+            height,
+            words_per_row,
+            bits: vec![0; words_per_row * height],
+        }
+    }
+
+    pub fn from_key(key: &str, rows: usize) -> Grid {
+        let keys = (0..rows)
+            .map(|x| format!("{}-{}", key, x))
+            .collect::<Vec<String>>();
+        Grid::from_rows(&aoc10::knot_hash_batch(&keys))
+    }
+
+    pub fn from_rows<T: AsRef<[u8]>>(rows: &[T]) -> Grid {
+        let width = rows.iter().map(|x| x.as_ref().len() * 8).max().unwrap_or(0);
+        let mut grid = Grid::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (index, byte) in row.as_ref().iter().enumerate() {
+                grid.bits[y * grid.words_per_row + index / 8] |=
+                    (*byte as u64) << (56 - 8 * (index % 8));
             }
         }
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let (word, mask) = self.position(x, y);
+        self.bits[word] & mask != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, used: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let (word, mask) = self.position(x, y);
+        if used {
+            self.bits[word] |= mask;
+        } else {
+            self.bits[word] &= !mask;
+        }
+    }
+
+    pub fn count_used(&self) -> usize {
+        self.bits.iter().map(|x| x.count_ones() as usize).sum()
+    }
+
+    pub fn regions(&self) -> Regions {
+        self.regions_with(Connectivity::Four)
+    }
+
+    pub fn regions_with(&self, connectivity: Connectivity) -> Regions {
+        let mut labels = vec![None; self.width * self.height];
+        let mut regions = vec![];
+        let mut stack = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !self.get(x, y) || labels[y * self.width + x].is_some() {
+                    continue;
+                }
+                let mut region = Region {
+                    id: regions.len(),
+                    size: 0,
+                    min_x: x,
+                    min_y: y,
+                    max_x: x,
+                    max_y: y,
+                };
+                labels[y * self.width + x] = Some(region.id);
+                stack.push((x, y));
+                while let Some((x, y)) = stack.pop() {
+                    region.add(x, y);
+                    for (x, y) in self.neighbours(x, y, connectivity) {
+                        if self.get(x, y) && labels[y * self.width + x].is_none() {
+                            labels[y * self.width + x] = Some(region.id);
+                            stack.push((x, y));
+                        }
+                    }
+                }
+                regions.push(region);
+            }
+        }
+        Regions {
+            width: self.width,
+            labels,
+            regions,
+        }
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let mut map = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                map.push(if self.get(x, y) { '#' } else { '.' });
+            }
+            map.push('\n');
+        }
+        map
+    }
+
+    pub fn to_pbm(&self) -> String {
+        let mut image = format!("P1\n{} {}\n", self.width, self.height);
+        for y in 0..self.height {
+            let row = (0..self.width).map(|x| if self.get(x, y) { "1" } else { "0" });
+            push_plain_row(&mut image, row);
+        }
+        image
+    }
+
+    fn position(&self, x: usize, y: usize) -> (usize, u64) {
+        (y * self.words_per_row + x / 64, 1 << (63 - x % 64))
+    }
+
+    fn neighbours(
+        &self,
+        x: usize,
+        y: usize,
+        connectivity: Connectivity,
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (self.width, self.height);
+        connectivity.offsets().iter().filter_map(move |&(dx, dy)| {
+            let x = x.checked_add_signed(dx)?;
+            let y = y.checked_add_signed(dy)?;
+            if x < width && y < height { Some((x, y)) } else { None }
+        })
     }
-    region_count
 }
 
-fn remove_neighbours(memory: &mut Vec<Vec<char>>, x: usize, y: usize) {
-    if memory[x][y] == '1' {
-        memory[x][y] = '0';
-        if x != 0 {
-            remove_neighbours(memory, x - 1, y);
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Region {
+    pub id: usize,
+    pub size: usize,
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+impl Region {
+    fn add(&mut self, x: usize, y: usize) {
+        self.size += 1;
+        self.min_x = cmp::min(self.min_x, x);
+        self.min_y = cmp::min(self.min_y, y);
+        self.max_x = cmp::max(self.max_x, x);
+        self.max_y = cmp::max(self.max_y, y);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Regions {
+    width: usize,
+    labels: Vec<Option<usize>>,
+    regions: Vec<Region>,
+}
+
+impl Regions {
+    pub fn count(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub fn label(&self, x: usize, y: usize) -> Option<usize> {
+        if x >= self.width {
+            return None;
         }
-        if x != 127 {
-            remove_neighbours(memory, x + 1, y);
+        *self.labels.get(y * self.width + x)?
+    }
+
+    pub fn region_at(&self, x: usize, y: usize) -> Option<&Region> {
+        Some(&self.regions[self.label(x, y)?])
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let symbols = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
+            .chars()
+            .collect::<Vec<char>>();
+        let colouring = self.colouring(symbols.len());
+        let mut map = String::with_capacity(self.labels.len() + self.height());
+        for row in self.rows() {
+            for label in row.iter() {
+                map.push(match *label {
+                    Some(id) => symbols[colouring[id]],
+                    None => '.',
+                });
+            }
+            map.push('\n');
+        }
+        map
+    }
+
+    pub fn to_ppm(&self) -> String {
+        let mut image = format!("P3\n{} {}\n255\n", self.width, self.height());
+        for row in self.rows() {
+            let row = row.iter().map(|label| {
+                let (r, g, b) = match *label {
+                    Some(id) => region_colour(id),
+                    None => (0, 0, 0),
+                };
+                format!("{} {} {}", r, g, b)
+            });
+            push_plain_row(&mut image, row);
         }
-        if y != 0 {
-            remove_neighbours(memory, x, y - 1);
+        image
+    }
+
+    // Greedy colouring over regions that touch, diagonals included, so
+    // neighbouring regions never share a symbol. A region touching more
+    // regions than there are symbols falls back to reusing one.
+    fn colouring(&self, colours: usize) -> Vec<usize> {
+        let mut touching = vec![BTreeSet::new(); self.regions.len()];
+        for y in 0..self.height() {
+            for x in 0..self.width {
+                let id = match self.label(x, y) {
+                    Some(id) => id,
+                    None => continue,
+                };
+                for &(dx, dy) in Connectivity::Eight.offsets().iter() {
+                    let other = x.checked_add_signed(dx)
+                        .zip(y.checked_add_signed(dy))
+                        .and_then(|(x, y)| self.label(x, y));
+                    if let Some(other) = other.filter(|other| *other != id) {
+                        touching[id].insert(other);
+                    }
+                }
+            }
         }
-        if y != 127 {
-            remove_neighbours(memory, x, y + 1);
+        let mut colouring: Vec<usize> = Vec::with_capacity(self.regions.len());
+        for (id, neighbours) in touching.iter().enumerate() {
+            let used = neighbours
+                .iter()
+                .filter(|x| **x < id)
+                .map(|x| colouring[*x])
+                .collect::<BTreeSet<usize>>();
+            colouring.push((0..colours).find(|x| !used.contains(x)).unwrap_or(id % colours));
         }
+        colouring
+    }
+
+    fn height(&self) -> usize {
+        self.labels.len().checked_div(self.width).unwrap_or(0)
     }
+
+    fn rows(&self) -> impl Iterator<Item = &[Option<usize>]> {
+        self.labels.chunks(cmp::max(self.width, 1))
+    }
+}
+
+// Plain Netpbm files should not have lines longer than 70 characters.
+fn push_plain_row<I, T>(image: &mut String, values: I)
+where
+    I: Iterator<Item = T>,
+    T: AsRef<str>,
+{
+    let mut line_length = 0;
+    for value in values {
+        let value = value.as_ref();
+        if line_length > 0 && line_length + 1 + value.len() > 70 {
+            image.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            image.push(' ');
+            line_length += 1;
+        }
+        image.push_str(value);
+        line_length += value.len();
+    }
+    image.push('\n');
 }
 
-fn create_memory_map(input: &str) -> Vec<Vec<u8>> {
-    (0..128)
-        .map(|x| aoc10::knot_tying_hash_raw(&format!("{}-{}", input, x)))
-        .collect()
+fn region_colour(id: usize) -> (u8, u8, u8) {
+    let hash = (id as u32).wrapping_add(1).wrapping_mul(2654435761);
+    (
+        55 + (hash >> 24) as u8 % 200,
+        55 + (hash >> 16) as u8 % 200,
+        55 + (hash >> 8) as u8 % 200,
+    )
 }
 
 #[test]
@@ -58,3 +347,105 @@ fn test_examples() {
     assert_eq!(8108, count_used_squares("flqrgnkx"));
     assert_eq!(1242, count_regions("flqrgnkx"));
 }
+
+#[test]
+fn test_grid() {
+    let grid = Grid::from_rows(&[vec![0b1100_0001], vec![0b0100_0001], vec![0b0000_0100]]);
+    assert_eq!((8, 3), (grid.width(), grid.height()));
+    assert_eq!(6, grid.count_used());
+    assert!(grid.get(0, 0));
+    assert!(!grid.get(0, 1));
+    assert!(!grid.get(8, 0));
+    let regions = grid.regions();
+    assert_eq!(3, regions.count());
+    assert_eq!(
+        Some(&Region {
+            id: 0,
+            size: 3,
+            min_x: 0,
+            min_y: 0,
+            max_x: 1,
+            max_y: 1,
+        }),
+        regions.region_at(1, 1)
+    );
+    assert_eq!(Some(1), regions.label(7, 1));
+    assert_eq!(Some(2), regions.label(5, 2));
+    assert_eq!(None, regions.label(2, 0));
+    assert_eq!(None, regions.label(9, 0));
+
+    let mut grid = Grid::new(70, 2);
+    grid.set(65, 1, true);
+    grid.set(64, 1, true);
+    grid.set(64, 1, false);
+    assert_eq!(1, grid.count_used());
+    assert!(grid.get(65, 1));
+    assert_eq!(Some(0), grid.regions().label(65, 1));
+}
+
+#[test]
+fn test_connectivity() {
+    let grid = Grid::from_rows(&[vec![0b1010_0000], vec![0b0100_0000], vec![0b1000_0001]]);
+    assert_eq!(5, grid.regions_with(Connectivity::Four).count());
+    let regions = grid.regions_with(Connectivity::Eight);
+    assert_eq!(2, regions.count());
+    assert_eq!(4, regions.regions()[0].size);
+    assert_eq!(Some(1), regions.label(7, 2));
+    assert!(count_regions_with("flqrgnkx", Connectivity::Eight) < 1242);
+}
+
+#[test]
+fn test_large_region() {
+    let size = 1024;
+    let grid = Grid::from_rows(&vec![vec![0xff; size / 8]; size]);
+    let regions = grid.regions();
+    assert_eq!(1, regions.count());
+    assert_eq!(size * size, regions.regions()[0].size);
+}
+
+#[test]
+fn test_render() {
+    let grid = Grid::from_rows(&[vec![0b1100_0001], vec![0b0100_0001]]);
+    assert_eq!("##.....#\n.#.....#\n", grid.to_ascii());
+    assert_eq!(
+        "P1\n8 2\n1 1 0 0 0 0 0 1\n0 1 0 0 0 0 0 1\n",
+        grid.to_pbm()
+    );
+    let regions = grid.regions();
+    assert_eq!("00.....0\n.0.....0\n", regions.to_ascii());
+    let image = regions.to_ppm();
+    assert!(image.starts_with("P3\n8 2\n255\n"));
+    assert!(image.lines().all(|x| x.len() <= 70));
+    assert_eq!(4 + 8 * 2 * 3, image.split_whitespace().count());
+    assert!(region_colour(0) != region_colour(1));
+    assert_eq!("", Grid::new(0, 0).regions().to_ascii());
+
+    let grid = Grid::from_rows(&[vec![0b1010_0000], vec![0b0100_0000]]);
+    assert_eq!("0.0.....\n.1......\n", grid.regions().to_ascii());
+
+    let grid = Grid::from_key("flqrgnkx", 128);
+    assert!(grid.to_pbm().lines().all(|x| x.len() <= 70));
+    assert_eq!(3 + 128 * 128, grid.to_pbm().split_whitespace().count());
+    let regions = grid.regions();
+    let map = regions
+        .to_ascii()
+        .lines()
+        .map(|x| x.chars().collect())
+        .collect::<Vec<Vec<char>>>();
+    for y in 0..128 {
+        for x in 0..128 {
+            for &(dx, dy) in Connectivity::Eight.offsets().iter() {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0 || ny < 0 || nx >= 128 || ny >= 128 {
+                    continue;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                if regions.label(x, y).is_some() && regions.label(nx, ny).is_some() &&
+                    regions.label(x, y) != regions.label(nx, ny)
+                {
+                    assert!(map[y][x] != map[ny][nx]);
+                }
+            }
+        }
+    }
+}
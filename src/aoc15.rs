@@ -1,49 +1,246 @@
+use std::cmp;
+use std::iter;
+use std::sync::mpsc;
+use std::thread;
+
+pub const FACTOR_A: u64 = 16807;
+pub const FACTOR_B: u64 = 48271;
+pub const MODULUS: u64 = 2147483647;
+
+const BATCH_SIZE: usize = 1 << 16;
 
 pub fn count_matches(init_a: &str, init_b: &str) -> Option<u32> {
-    let mut generator_a = Generator::new(init_a.trim().parse().ok()?, 16807, 1);
-    let mut generator_b = Generator::new(init_b.trim().parse().ok()?, 48271, 1);
-    Some((0..40000000).fold(0, |acc, _| {
-        let a = generator_a.next() & 0xffff;
-        let b = generator_b.next() & 0xffff;
-        if a == b { acc + 1 } else { acc }
-    }))
+    let generator_a = Generator::new(init_a.trim().parse().ok()?, FACTOR_A, MODULUS, 1)?;
+    let generator_b = Generator::new(init_b.trim().parse().ok()?, FACTOR_B, MODULUS, 1)?;
+    Some(Judge::new(40000000, 0xffff).count_split(&generator_a, &generator_b) as u32)
 }
 
 pub fn count_filtered(init_a: &str, init_b: &str) -> Option<u32> {
-    let mut generator_a = Generator::new(init_a.trim().parse().ok()?, 16807, 4);
-    let mut generator_b = Generator::new(init_b.trim().parse().ok()?, 48271, 8);
-    Some((0..5000000).fold(0, |acc, _| {
-        let a = generator_a.next() & 0xffff;
-        let b = generator_b.next() & 0xffff;
-        if a == b { acc + 1 } else { acc }
-    }))
+    let generator_a = Generator::new(init_a.trim().parse().ok()?, FACTOR_A, MODULUS, 4)?;
+    let generator_b = Generator::new(init_b.trim().parse().ok()?, FACTOR_B, MODULUS, 8)?;
+    Some(Judge::new(5000000, 0xffff).count_split(&generator_a, &generator_b) as u32)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Judge {
+    pairs: usize,
+    mask: u64,
+}
+
+impl Judge {
+    pub fn new(pairs: usize, mask: u64) -> Judge {
+        Judge { pairs, mask }
+    }
+
+    pub fn count<A, B>(&self, mut generator_a: A, mut generator_b: B) -> usize
+    where
+        A: Iterator<Item = u64>,
+        B: Iterator<Item = u64>,
+    {
+        let mut matches = 0;
+        for _ in 0..self.pairs {
+            match (generator_a.next(), generator_b.next()) {
+                (Some(a), Some(b)) => if (a ^ b) & self.mask == 0 {
+                    matches += 1;
+                },
+                _ => break,
+            }
+        }
+        matches
+    }
+
+    pub fn count_parallel<A, B>(&self, generator_a: A, generator_b: B) -> usize
+    where
+        A: Iterator<Item = u64> + Send,
+        B: Iterator<Item = u64> + Send,
+    {
+        let (pairs, mask) = (self.pairs, self.mask);
+        thread::scope(|scope| {
+            let (sender_a, batches_a) = mpsc::sync_channel(4);
+            let (sender_b, batches_b) = mpsc::sync_channel(4);
+            scope.spawn(move || send_batches(generator_a.take(pairs), mask, &sender_a));
+            scope.spawn(move || send_batches(generator_b.take(pairs), mask, &sender_b));
+            batches_a
+                .iter()
+                .zip(batches_b.iter())
+                .map(|(a, b): (Vec<u64>, Vec<u64>)| {
+                    a.iter().zip(b.iter()).filter(|&(a, b)| a == b).count()
+                })
+                .sum()
+        })
+    }
+
+    pub fn count_split(&self, generator_a: &Generator, generator_b: &Generator) -> usize {
+        if generator_a.filter != 1 || generator_b.filter != 1 {
+            let (generator_a, generator_b) = (generator_a.clone(), generator_b.clone());
+            return if generator_a.is_mersenne() && generator_b.is_mersenne() {
+                self.count_parallel(generator_a.mersenne_steps(), generator_b.mersenne_steps())
+            } else {
+                self.count_parallel(generator_a, generator_b)
+            };
+        }
+        let workers = thread::available_parallelism().map_or(1, |x| x.get());
+        let chunk_size = cmp::max(1, self.pairs.div_ceil(workers));
+        thread::scope(|scope| {
+            let handles = (0..self.pairs)
+                .step_by(chunk_size)
+                .map(|start| {
+                    let judge = Judge::new(cmp::min(chunk_size, self.pairs - start), self.mask);
+                    let generator_a = generator_a.clone().jump(start as u64);
+                    let generator_b = generator_b.clone().jump(start as u64);
+                    scope.spawn(move || judge.count_generators(generator_a, generator_b))
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().map(|x| x.join().unwrap()).sum()
+        })
+    }
+
+    fn count_generators(&self, generator_a: Generator, generator_b: Generator) -> usize {
+        if generator_a.is_mersenne() && generator_b.is_mersenne() {
+            self.count(generator_a.mersenne_steps(), generator_b.mersenne_steps())
+        } else {
+            self.count(generator_a, generator_b)
+        }
+    }
+}
+
+fn send_batches<I>(mut generator: I, mask: u64, sender: &mpsc::SyncSender<Vec<u64>>)
+where
+    I: Iterator<Item = u64>,
+{
+    loop {
+        let batch = generator
+            .by_ref()
+            .take(BATCH_SIZE)
+            .map(|x| x & mask)
+            .collect::<Vec<u64>>();
+        if batch.is_empty() || sender.send(batch).is_err() {
+            return;
+        }
+    }
 }
 
-struct Generator {
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Generator {
     previous: u64,
     factor: u64,
     modulo: u64,
-    filter: u32,
+    filter: u64,
+    reduction: Reduction,
 }
 
 impl Generator {
-    fn new(initial_value: u32, factor: u32, filter: u32) -> Generator {
-        Generator {
-            previous: initial_value as u64,
-            factor: factor as u64,
-            modulo: 2147483647,
-            filter: filter,
+    pub fn new(initial_value: u64, factor: u64, modulo: u64, filter: u64) -> Option<Generator> {
+        if modulo == 0 || filter == 0 {
+            return None;
+        }
+        let reduction = if modulo == MODULUS {
+            Reduction::Mersenne
+        } else if modulo <= 1 << 32 {
+            Reduction::Native
+        } else {
+            Reduction::Wide
+        };
+        Some(Generator {
+            previous: initial_value % modulo,
+            factor: factor % modulo,
+            modulo,
+            filter,
+            reduction,
+        })
+    }
+
+    // Advances the raw sequence; values rejected by the filter count as steps too.
+    pub fn jump(mut self, steps: u64) -> Generator {
+        let mut factor = self.factor;
+        let mut steps = steps;
+        while steps > 0 {
+            if steps & 1 == 1 {
+                self.previous = self.reduction.mul_mod(self.previous, factor, self.modulo);
+            }
+            factor = self.reduction.mul_mod(factor, factor, self.modulo);
+            steps >>= 1;
         }
+        self
+    }
+
+    fn is_mersenne(&self) -> bool {
+        self.reduction == Reduction::Mersenne
+    }
+
+    // Picks the reduction once for the whole run; `next` has to pick it again
+    // for every value, which keeps the hot loop from being optimised.
+    fn mersenne_steps(mut self) -> impl Iterator<Item = u64> {
+        iter::from_fn(move || self.advance(|value, factor, _| mersenne_mul(value, factor)))
     }
 
-    fn next(&mut self) -> u32 {
+    // Brent's cycle detection: coming back to a saved value means a whole
+    // cycle went by without passing the filter, so no value ever will.
+    #[inline(always)]
+    fn advance<F: Fn(u64, u64, u64) -> u64>(&mut self, mul_mod: F) -> Option<u64> {
+        if self.filter == 1 {
+            self.previous = mul_mod(self.previous, self.factor, self.modulo);
+            return Some(self.previous);
+        }
+        let mut saved = self.previous;
+        let (mut power, mut length) = (1u64, 0u64);
         loop {
-            self.previous = (self.previous * self.factor) % self.modulo;
-            if self.previous as u32 % self.filter == 0 {
-                break;
+            self.previous = mul_mod(self.previous, self.factor, self.modulo);
+            if self.previous.is_multiple_of(self.filter) {
+                return Some(self.previous);
             }
+            if self.previous == saved {
+                return None;
+            }
+            length += 1;
+            if length == power {
+                saved = self.previous;
+                power *= 2;
+                length = 0;
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Reduction {
+    Mersenne,
+    Native,
+    Wide,
+}
+
+impl Reduction {
+    fn mul_mod(&self, value: u64, factor: u64, modulo: u64) -> u64 {
+        match *self {
+            Reduction::Mersenne => mersenne_mul(value, factor),
+            Reduction::Native => value * factor % modulo,
+            Reduction::Wide => wide_mul(value, factor, modulo),
+        }
+    }
+}
+
+// 2^31 ≡ 1 (mod 2^31 - 1), so the high bits of the product fold onto the
+// low bits. Both inputs are below the modulus, so one fold leaves less than
+// twice the modulus.
+fn mersenne_mul(value: u64, factor: u64) -> u64 {
+    let product = value * factor;
+    let folded = (product & MODULUS) + (product >> 31);
+    if folded >= MODULUS { folded - MODULUS } else { folded }
+}
+
+fn wide_mul(value: u64, factor: u64, modulo: u64) -> u64 {
+    (value as u128 * factor as u128 % modulo as u128) as u64
+}
+
+impl Iterator for Generator {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        match self.reduction {
+            Reduction::Mersenne => self.advance(|value, factor, _| mersenne_mul(value, factor)),
+            Reduction::Native => self.advance(|value, factor, modulo| value * factor % modulo),
+            Reduction::Wide => self.advance(wide_mul),
         }
-        self.previous as u32
     }
 }
 
@@ -52,3 +249,97 @@ fn test_examples() {
     assert_eq!(Some(588), count_matches("65", "8921"));
     assert_eq!(Some(309), count_filtered("65", "8921"));
 }
+
+#[test]
+fn test_generator() {
+    assert_eq!(
+        vec![1092455, 1181022009, 245556042, 1744312007, 1352636452],
+        Generator::new(65, FACTOR_A, MODULUS, 1)
+            .unwrap()
+            .take(5)
+            .collect::<Vec<u64>>()
+    );
+    assert_eq!(
+        vec![1233683848, 862516352, 1159784568, 1616057672, 412269392],
+        Generator::new(8921, FACTOR_B, MODULUS, 8)
+            .unwrap()
+            .take(5)
+            .collect::<Vec<u64>>()
+    );
+    assert_eq!(
+        vec![3, 2, 6, 4, 5, 1],
+        Generator::new(1, 3, 7, 1).unwrap().take(6).collect::<Vec<u64>>()
+    );
+    assert_eq!(
+        Some((1 << 63) + 56),
+        Generator::new(3, u64::MAX / 2, u64::MAX - 58, 1)
+            .unwrap()
+            .next()
+    );
+    assert_eq!(None, Generator::new(1, 3, 0, 1));
+    assert_eq!(None, Generator::new(1, 3, 7, 0));
+    assert_eq!(None, Generator::new(1, 3, 7, 8).unwrap().next());
+    assert_eq!(None, Generator::new(2, 2, 12, 3).unwrap().next());
+    assert_eq!(Some(4), Generator::new(2, 2, 12, 4).unwrap().next());
+    assert_eq!(
+        0,
+        Judge::new(10, 0xffff).count(
+            Generator::new(1, 3, 7, 8).unwrap(),
+            Generator::new(1, 3, 7, 1).unwrap()
+        )
+    );
+
+    let generator_a = Generator::new(65, FACTOR_A, MODULUS, 1).unwrap();
+    let generator_b = Generator::new(8921, FACTOR_B, MODULUS, 1).unwrap();
+    assert_eq!(1, Judge::new(5, 0xffff).count(generator_a.clone(), generator_b.clone()));
+    assert_eq!(4, Judge::new(5, 0xf).count(generator_a, generator_b));
+}
+
+#[test]
+fn test_fast_paths() {
+    for &value in [0, 1, 65, 8921, MODULUS - 1, MODULUS / 2].iter() {
+        for &factor in [0, 1, FACTOR_A, FACTOR_B, MODULUS - 1].iter() {
+            assert_eq!(value * factor % MODULUS, mersenne_mul(value, factor));
+        }
+    }
+    let generator_a = Generator::new(65, FACTOR_A, MODULUS, 4).unwrap();
+    let generator_b = Generator::new(8921, FACTOR_B, MODULUS, 8).unwrap();
+    let judge = Judge::new(100000, 0xff);
+    assert_eq!(
+        judge.count(generator_a.clone(), generator_b.clone()),
+        judge.count_parallel(generator_a.clone(), generator_b.clone())
+    );
+    assert_eq!(
+        1,
+        Judge::new(3, 0xffff).count_parallel(vec![1, 2, 3].into_iter(), vec![1, 5].into_iter())
+    );
+    assert_eq!(0, Judge::new(0, 0xffff).count_parallel(generator_a, generator_b));
+}
+
+#[test]
+fn test_jump() {
+    let generator = Generator::new(65, FACTOR_A, MODULUS, 1).unwrap();
+    assert_eq!(generator.clone().nth(1000), generator.clone().jump(1000).next());
+    assert_eq!(generator, generator.clone().jump(0));
+    assert_eq!(Some(4), Generator::new(5, 3, 7, 2).unwrap().jump(4).next());
+    let generator = Generator::new(65, FACTOR_A, MODULUS, 4).unwrap();
+    assert_eq!(Some(1352636452), generator.clone().jump(3).next());
+    assert_eq!(Some(1980017072), generator.clone().nth(3));
+    let generator = Generator::new(3, u64::MAX / 2, u64::MAX - 58, 1).unwrap();
+    assert_eq!(generator.clone().nth(77), generator.jump(77).next());
+
+    let generator_a = Generator::new(65, FACTOR_A, MODULUS, 1).unwrap();
+    let generator_b = Generator::new(8921, FACTOR_B, MODULUS, 1).unwrap();
+    for &pairs in [0, 1, 7, 100003].iter() {
+        let judge = Judge::new(pairs, 0xff);
+        assert_eq!(
+            judge.count(generator_a.clone(), generator_b.clone()),
+            judge.count_split(&generator_a, &generator_b)
+        );
+    }
+    let generator_b = Generator::new(8921, FACTOR_B, MODULUS, 8).unwrap();
+    assert_eq!(
+        Judge::new(1000, 0xff).count(generator_a.clone(), generator_b.clone()),
+        Judge::new(1000, 0xff).count_split(&generator_a, &generator_b)
+    );
+}
@@ -1,108 +1,378 @@
-use std::str::FromStr;
-use utils;
+use std::cmp;
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+const DANCERS: &str = "abcdefghijklmnop";
 
 pub fn dance(moves: &str) -> Option<String> {
-    Some(
-        one_dance(&parse_moves(moves)?, "abcdefghijklmnop".chars().collect())
-            .iter()
-            .collect(),
-    )
+    dance_with(moves, DANCERS, 1).ok()
 }
 
 pub fn dance_whole_night(moves: &str) -> Option<String> {
-    let moves = parse_moves(moves)?;
-    Some(
-        (0..(1000000000 % find_loop(&moves)))
-            .fold("abcdefghijklmnop".chars().collect(), |dancers, _| {
-                one_dance(&moves, dancers)
-            })
-            .iter()
-            .collect(),
-    )
+    dance_with(moves, DANCERS, 1000000000).ok()
 }
 
-fn one_dance(dance: &Vec<Move>, dancers: Vec<char>) -> Vec<char> {
-    dance.iter().fold(dancers, |acc, step| step.step(&acc))
+pub fn dance_with(moves: &str, dancers: &str, dances: u64) -> Result<String, DanceError> {
+    Ok(Dance::compile(&parse_moves(moves)?, dancers)?.pow(dances).line())
 }
 
-fn find_loop(moves: &Vec<Move>) -> u32 {
-    let mut steps = 0;
-    let mut dancers = "abcdefghijklmnop".chars().collect();
-    loop {
-        steps += 1;
-        dancers = one_dance(&moves, dancers);
-        if dancers.iter().collect::<String>() == "abcdefghijklmnop" {
-            return steps;
-        }
+pub fn line_of(count: usize) -> Result<String, DanceError> {
+    if count > 26 {
+        return Err(DanceError::TooManyDancers(count));
     }
+    Ok((b'a'..).take(count).map(|x| x as char).collect())
 }
 
-fn parse_moves(moves: &str) -> Option<Vec<Move>> {
+pub fn parse_moves(moves: &str) -> Result<Vec<Move>, DanceError> {
     let mut dance = vec![];
-    for token in moves.split(',') {
-        dance.push(Move::from_str(token).ok()?);
+    for (index, token) in moves.trim().split(',').enumerate() {
+        dance.push(Move::from_str(token).map_err(|x| DanceError::InvalidMove(index + 1, x))?);
+    }
+    Ok(dance)
+}
+
+pub fn simplify(moves: &[Move], dancers: &str) -> Result<Vec<Move>, DanceError> {
+    Ok(Dance::compile(moves, dancers)?.to_moves())
+}
+
+pub fn format_moves(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DanceError {
+    InvalidMove(usize, MoveError),
+    TooManyDancers(usize),
+    DuplicateDancer(char),
+    PositionOutOfRange(usize, u32),
+    SpinOutOfRange(usize, u32),
+    UnknownDancer(usize, char),
+}
+
+impl fmt::Display for DanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DanceError::InvalidMove(index, ref error) => write!(f, "move {}, {}", index, error),
+            DanceError::TooManyDancers(count) => {
+                write!(f, "cannot name {} dancers with the letters a to z", count)
+            }
+            DanceError::DuplicateDancer(name) => {
+                write!(f, "dancer '{}' appears more than once in the line", name)
+            }
+            DanceError::PositionOutOfRange(index, position) => {
+                write!(f, "move {}: position {} is outside the line", index, position)
+            }
+            DanceError::SpinOutOfRange(index, length) => {
+                write!(f, "move {}: spin of {} is longer than the line", index, length)
+            }
+            DanceError::UnknownDancer(index, name) => {
+                write!(f, "move {}: no dancer is called '{}'", index, name)
+            }
+        }
     }
-    Some(dance)
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-enum Move {
+pub enum Move {
     Spin(u32),
     Exchange(u32, u32),
     Partner(char, char),
 }
 
-impl Move {
-    fn step(&self, dancers: &Vec<char>) -> Vec<char> {
-        match *self {
-            Move::Spin(length) => {
-                let mut moved = vec![];
-                let dancer_count = dancers.len();
-                for i in 0..dancer_count {
-                    moved.push(
-                        dancers[(i + dancers.len() - length as usize) % dancer_count],
-                    );
+// Spins and exchanges only move slots and partners only rename dancers, so
+// the two kinds of move commute and each can be tracked as its own permutation.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Dance {
+    dancers: Vec<char>,
+    positions: Vec<usize>,
+    labels: Vec<usize>,
+}
+
+impl Dance {
+    pub fn new(dancers: &str) -> Result<Dance, DanceError> {
+        let mut seen = HashSet::new();
+        for name in dancers.chars() {
+            if !seen.insert(name) {
+                return Err(DanceError::DuplicateDancer(name));
+            }
+        }
+        Ok(Dance::identity(dancers.chars().collect()))
+    }
+
+    pub fn compile(moves: &[Move], dancers: &str) -> Result<Dance, DanceError> {
+        let mut dance = Dance::new(dancers)?;
+        let size = dance.size();
+        for (index, step) in moves.iter().enumerate() {
+            match *step {
+                Move::Spin(length) => {
+                    if length as usize > size {
+                        return Err(DanceError::SpinOutOfRange(index + 1, length));
+                    }
+                    dance.positions.rotate_right(length as usize);
+                }
+                Move::Exchange(left, right) => {
+                    for &position in [left, right].iter() {
+                        if position as usize >= size {
+                            return Err(DanceError::PositionOutOfRange(index + 1, position));
+                        }
+                    }
+                    dance.positions.swap(left as usize, right as usize);
+                }
+                Move::Partner(left, right) => {
+                    let left = dance.label(left, index)?;
+                    let right = dance.label(right, index)?;
+                    for label in dance.labels.iter_mut() {
+                        if *label == left {
+                            *label = right;
+                        } else if *label == right {
+                            *label = left;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(dance)
+    }
+
+    pub fn size(&self) -> usize {
+        self.dancers.len()
+    }
+
+    pub fn then(&self, other: &Dance) -> Option<Dance> {
+        if self.dancers != other.dancers {
+            return None;
+        }
+        Some(Dance {
+            dancers: self.dancers.clone(),
+            positions: other.positions.iter().map(|x| self.positions[*x]).collect(),
+            labels: self.labels.iter().map(|x| other.labels[*x]).collect(),
+        })
+    }
+
+    pub fn pow(&self, times: u64) -> Dance {
+        let mut result = Dance::identity(self.dancers.clone());
+        let mut square = self.clone();
+        let mut times = times;
+        while times > 0 {
+            if times & 1 == 1 {
+                result = result.then(&square).unwrap();
+            }
+            square = square.then(&square).unwrap();
+            times >>= 1;
+        }
+        result
+    }
+
+    pub fn line(&self) -> String {
+        self.positions
+            .iter()
+            .map(|x| self.dancers[self.labels[*x]])
+            .collect()
+    }
+
+    pub fn to_moves(&self) -> Vec<Move> {
+        let size = self.size();
+        let mut moves = (0..cmp::max(size, 1))
+            .map(|spin| self.position_moves(spin))
+            .min_by_key(|x| x.len())
+            .unwrap();
+        let mut labels = (0..size).collect::<Vec<usize>>();
+        for index in 0..size {
+            let (left, right) = (labels[index], self.labels[index]);
+            if left == right {
+                continue;
+            }
+            for label in labels.iter_mut() {
+                if *label == left {
+                    *label = right;
+                } else if *label == right {
+                    *label = left;
                 }
-                moved
             }
-            Move::Exchange(left, right) => {
-                let mut moved = dancers.clone();
-                moved.swap(left as usize, right as usize);
-                moved
+            moves.push(Move::Partner(self.dancers[left], self.dancers[right]));
+        }
+        moves
+    }
+
+    // Fixes one slot per exchange, which needs the fewest exchanges for the
+    // permutation that is left after the spin.
+    fn position_moves(&self, spin: usize) -> Vec<Move> {
+        let mut positions = (0..self.size()).collect::<Vec<usize>>();
+        let mut moves = vec![];
+        if spin > 0 {
+            positions.rotate_right(spin);
+            moves.push(Move::Spin(spin as u32));
+        }
+        for index in 0..positions.len() {
+            if positions[index] == self.positions[index] {
+                continue;
             }
-            Move::Partner(left, right) => {
-                dancers
+            let other = index
+                + positions[index..]
                     .iter()
-                    .map(|x| if *x == left {
-                        right
-                    } else if *x == right {
-                        left
-                    } else {
-                        *x
-                    })
-                    .collect()
-            }
+                    .position(|x| *x == self.positions[index])
+                    .unwrap();
+            positions.swap(index, other);
+            moves.push(Move::Exchange(index as u32, other as u32));
+        }
+        moves
+    }
+
+    fn identity(dancers: Vec<char>) -> Dance {
+        Dance {
+            positions: (0..dancers.len()).collect(),
+            labels: (0..dancers.len()).collect(),
+            dancers,
+        }
+    }
+
+    fn label(&self, name: char, index: usize) -> Result<usize, DanceError> {
+        self.dancers
+            .iter()
+            .position(|x| *x == name)
+            .ok_or(DanceError::UnknownDancer(index + 1, name))
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Move::Spin(length) => write!(f, "s{}", length),
+            Move::Exchange(left, right) => write!(f, "x{}/{}", left, right),
+            Move::Partner(left, right) => write!(f, "p{}/{}", left, right),
         }
     }
 }
 
 impl FromStr for Move {
-    type Err = utils::Error;
+    type Err = MoveError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.chars().nth(0) {
-            Some('s') => Ok(Move::Spin(s.split_at(1).1.parse()?)),
+        let mut cursor = Cursor {
+            chars: s.chars().peekable(),
+            column: 1,
+        };
+        let parsed = match cursor.next() {
+            Some('s') => Move::Spin(cursor.number()?),
             Some('x') => {
-                let mut tokens = s.split_at(1).1.split('/');
-                Ok(Move::Exchange(
-                    tokens.next().ok_or(utils::Error)?.parse()?,
-                    tokens.next().ok_or(utils::Error)?.parse()?,
-                ))
+                let left = cursor.number()?;
+                cursor.expect('/')?;
+                Move::Exchange(left, cursor.number()?)
+            }
+            Some('p') => {
+                let left = cursor.name()?;
+                cursor.expect('/')?;
+                Move::Partner(left, cursor.name()?)
+            }
+            Some(other) => return Err(cursor.error_before(MoveErrorKind::UnknownMove(other))),
+            None => return Err(cursor.error(MoveErrorKind::Empty)),
+        };
+        if cursor.chars.peek().is_some() {
+            return Err(cursor.error(MoveErrorKind::TrailingInput));
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MoveError {
+    pub column: usize,
+    pub kind: MoveErrorKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MoveErrorKind {
+    Empty,
+    UnknownMove(char),
+    ExpectedNumber,
+    NumberTooLarge,
+    ExpectedName,
+    Expected(char),
+    TrailingInput,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: ", self.column)?;
+        match self.kind {
+            MoveErrorKind::Empty => write!(f, "empty move"),
+            MoveErrorKind::UnknownMove(name) => write!(f, "unknown move '{}'", name),
+            MoveErrorKind::ExpectedNumber => write!(f, "expected a number"),
+            MoveErrorKind::NumberTooLarge => write!(f, "number is too large"),
+            MoveErrorKind::ExpectedName => write!(f, "expected a dancer name"),
+            MoveErrorKind::Expected(expected) => write!(f, "expected '{}'", expected),
+            MoveErrorKind::TrailingInput => write!(f, "unexpected text after the move"),
+        }
+    }
+}
+
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn next(&mut self) -> Option<char> {
+        let next = self.chars.next();
+        if next.is_some() {
+            self.column += 1;
+        }
+        next
+    }
+
+    fn number(&mut self) -> Result<u32, MoveError> {
+        let start = self.column;
+        let mut number: Option<u32> = None;
+        while let Some(digit) = self.chars.peek().and_then(|x| x.to_digit(10)) {
+            self.next();
+            number = number
+                .unwrap_or(0)
+                .checked_mul(10)
+                .and_then(|x| x.checked_add(digit));
+            if number.is_none() {
+                return Err(MoveError {
+                    column: start,
+                    kind: MoveErrorKind::NumberTooLarge,
+                });
+            }
+        }
+        number.ok_or(self.error(MoveErrorKind::ExpectedNumber))
+    }
+
+    fn name(&mut self) -> Result<char, MoveError> {
+        match self.chars.peek() {
+            Some(&name) if name != '/' && name != ',' && !name.is_whitespace() => {
+                self.next();
+                Ok(name)
             }
-            Some('p') => Ok(Move::Partner(
-                s.chars().nth(1).ok_or(utils::Error)?,
-                s.chars().nth(3).ok_or(utils::Error)?,
-            )),
-            _ => Err(utils::Error),
+            _ => Err(self.error(MoveErrorKind::ExpectedName)),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), MoveError> {
+        if self.chars.peek() != Some(&expected) {
+            return Err(self.error(MoveErrorKind::Expected(expected)));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn error(&self, kind: MoveErrorKind) -> MoveError {
+        MoveError {
+            column: self.column,
+            kind,
+        }
+    }
+
+    fn error_before(&self, kind: MoveErrorKind) -> MoveError {
+        MoveError {
+            column: self.column - 1,
+            kind,
         }
     }
 }
@@ -117,10 +387,158 @@ fn test_examples() {
 
 #[test]
 fn test_from_str() {
-    assert_eq!(Err(utils::Error), Move::from_str(""));
-    assert_eq!(Err(utils::Error), Move::from_str("a3/4"));
-    assert_eq!(Err(utils::Error), Move::from_str("sa"));
     assert_eq!(Ok(Move::Spin(10)), Move::from_str("s10"));
     assert_eq!(Ok(Move::Exchange(3, 10)), Move::from_str("x3/10"));
     assert_eq!(Ok(Move::Partner('a', 'c')), Move::from_str("pa/c"));
+    let error = |column, kind| Err(MoveError { column, kind });
+    assert_eq!(error(1, MoveErrorKind::Empty), Move::from_str(""));
+    assert_eq!(error(1, MoveErrorKind::UnknownMove('a')), Move::from_str("a3/4"));
+    assert_eq!(error(2, MoveErrorKind::ExpectedNumber), Move::from_str("sa"));
+    assert_eq!(error(2, MoveErrorKind::ExpectedNumber), Move::from_str("s"));
+    assert_eq!(error(4, MoveErrorKind::TrailingInput), Move::from_str("s10x"));
+    assert_eq!(error(2, MoveErrorKind::NumberTooLarge), Move::from_str("s99999999999"));
+    assert_eq!(error(3, MoveErrorKind::Expected('/')), Move::from_str("x3-4"));
+    assert_eq!(error(4, MoveErrorKind::ExpectedNumber), Move::from_str("x3/"));
+    assert_eq!(error(3, MoveErrorKind::Expected('/')), Move::from_str("pa-b"));
+    assert_eq!(error(3, MoveErrorKind::Expected('/')), Move::from_str("pab/c"));
+    assert_eq!(error(5, MoveErrorKind::TrailingInput), Move::from_str("pa/bc"));
+    assert_eq!(error(2, MoveErrorKind::ExpectedName), Move::from_str("p/b"));
+    assert_eq!(error(4, MoveErrorKind::ExpectedName), Move::from_str("pa/"));
+    assert_eq!(error(1, MoveErrorKind::UnknownMove(' ')), Move::from_str(" s1"));
+    assert_eq!(
+        "column 3: expected '/'",
+        Move::from_str("pa-b").unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let text = "s1,x3/4,pe/b,s0,x15/0,pz/é";
+    let moves = parse_moves(text).unwrap();
+    assert_eq!(text, format_moves(&moves));
+    assert_eq!(Ok(moves.clone()), parse_moves(&format_moves(&moves)));
+    assert_eq!(Ok(moves), parse_moves("s1,x3/4,pe/b,s0,x15/0,pz/é\n"));
+    assert_eq!("x3/4", Move::from_str("x03/4").unwrap().to_string());
+    assert_eq!(
+        Err(DanceError::InvalidMove(
+            2,
+            MoveError {
+                column: 3,
+                kind: MoveErrorKind::Expected('/'),
+            }
+        )),
+        parse_moves("s1,pa-b")
+    );
+    assert_eq!(
+        "move 2, column 3: expected '/'",
+        parse_moves("s1,pa-b").unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn test_dance() {
+    let moves = parse_moves("s1,x3/4,pe/b").unwrap();
+    let dance = Dance::compile(&moves, "abcde").unwrap();
+    assert_eq!("baedc", dance.line());
+    assert_eq!("ceadb", dance.pow(2).line());
+    assert_eq!(Some(dance.pow(2)), dance.then(&dance));
+    assert_eq!("abcde", dance.pow(0).line());
+    assert_eq!(Ok(dance.pow(4)), Dance::new("abcde"));
+    assert_eq!(None, dance.then(&Dance::new("abcdef").unwrap()));
+    assert_eq!("", Dance::compile(&[Move::Spin(0)], "").unwrap().line());
+    assert_eq!("abcde", Dance::compile(&[Move::Spin(5)], "abcde").unwrap().line());
+    assert_eq!(
+        Err(DanceError::SpinOutOfRange(2, 6)),
+        Dance::compile(&[Move::Spin(1), Move::Spin(6)], "abcde")
+    );
+
+    let moves = parse_moves("s3,pa/p,x0/15,pc/d,x2/7,s11,pa/c").unwrap();
+    let dance = Dance::compile(&moves, DANCERS).unwrap();
+    let mut repeated = Dance::new(DANCERS).unwrap();
+    for _ in 0..1000 {
+        repeated = repeated.then(&dance).unwrap();
+    }
+    assert_eq!(repeated, dance.pow(1000));
+}
+
+#[test]
+fn test_dance_with() {
+    assert_eq!(Ok(String::from("baedc")), dance_with("s1,x3/4,pe/b", "abcde", 1));
+    assert_eq!(Ok(String::from("ceadb")), dance_with("s1,x3/4,pe/b", "abcde", 2));
+    assert_eq!(
+        Ok(String::from("ceadb")),
+        dance_with("s1,x3/4,pe/b", "abcde", 1000000002)
+    );
+    assert_eq!(Ok(String::from("xzy")), dance_with("s1,px/z", "xyz", 1));
+    assert_eq!(Ok(String::from("abcde")), line_of(5));
+    assert_eq!(Ok(String::new()), line_of(0));
+    assert_eq!(Err(DanceError::TooManyDancers(27)), line_of(27));
+    assert_eq!(
+        Err(DanceError::PositionOutOfRange(2, 5)),
+        dance_with("s1,x3/5,pe/b", "abcde", 1)
+    );
+    assert_eq!(
+        Err(DanceError::UnknownDancer(3, 'p')),
+        dance_with("s1,x3/4,pe/p", "abcde", 1)
+    );
+    assert!(dance_with("s1,q,pe/b", "abcde", 1).is_err());
+    assert_eq!(Err(DanceError::DuplicateDancer('a')), dance_with("s1", "aba", 1));
+    assert_eq!(
+        "move 3: no dancer is called 'p'",
+        DanceError::UnknownDancer(3, 'p').to_string()
+    );
+    assert_eq!(
+        Err(DanceError::SpinOutOfRange(1, 16)),
+        dance_with("s16,x3/4", "abcde", 1)
+    );
+    assert_eq!(
+        "move 1: spin of 16 is longer than the line",
+        DanceError::SpinOutOfRange(1, 16).to_string()
+    );
+}
+
+#[test]
+fn test_simplify() {
+    assert_eq!(Ok(vec![]), simplify(&parse_moves("s1,s2,s2").unwrap(), "abcde"));
+    assert_eq!(
+        Ok(vec![]),
+        simplify(&parse_moves("x0/1,pa/b,x1/0,pb/a").unwrap(), "abcde")
+    );
+    assert_eq!(
+        Ok(vec![Move::Spin(3), Move::Partner('a', 'c')]),
+        simplify(&parse_moves("s1,pa/b,s2,pb/c,pa/b").unwrap(), "abcde")
+    );
+    assert_eq!(
+        Ok(vec![Move::Exchange(0, 3)]),
+        simplify(&parse_moves("x0/1,x1/3,x0/1").unwrap(), "abcde")
+    );
+    assert_eq!(
+        Err(DanceError::PositionOutOfRange(1, 7)),
+        simplify(&[Move::Exchange(7, 1)], "abcde")
+    );
+    assert_eq!(Ok(vec![]), simplify(&[Move::Spin(0)], ""));
+    assert_eq!(
+        Err(DanceError::SpinOutOfRange(1, 4)),
+        simplify(&[Move::Spin(4)], "")
+    );
+
+    let mut moves = vec![];
+    let mut seed = 7u32;
+    for _ in 0..5000 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let (a, b) = ((seed >> 8) % 16, (seed >> 16) % 16);
+        moves.push(match (seed >> 4) % 3 {
+            0 => Move::Spin(a),
+            1 => Move::Exchange(a, b),
+            _ => Move::Partner((b'a' + a as u8) as char, (b'a' + b as u8) as char),
+        });
+    }
+    let simplified = simplify(&moves, DANCERS).unwrap();
+    assert!(simplified.len() < 2 * DANCERS.len());
+    assert_eq!(
+        Dance::compile(&moves, DANCERS),
+        Dance::compile(&simplified, DANCERS)
+    );
+    let dance = Dance::compile(&moves, DANCERS).unwrap();
+    assert_eq!(Ok(dance.pow(3)), Dance::compile(&dance.pow(3).to_moves(), DANCERS));
 }
@@ -1,27 +1,173 @@
+const TRACKED_PREFIX: usize = 64;
+const CHUNK_SIZE: usize = 512;
 
 pub fn spinlock(step_length: &str) -> Option<u32> {
     let step_length = step_length.trim().parse::<usize>().ok()?;
-    let mut pointer = 0;
-    let mut buffer = vec![0];
-    for i in 1..2018 {
-        pointer = (pointer + step_length) % buffer.len() + 1;
-        buffer.insert(pointer, i)
-    }
-    Some(buffer[(pointer + 1) % buffer.len()])
+    Spinlock::new(step_length, 2017).value_after(2017)
 }
 
 pub fn angry_spinlock(step_length: &str) -> Option<u32> {
-    let step_length = step_length.trim().parse::<u32>().ok()?;
-    let mut pointer = 0;
-    (1..50000000)
-        .filter(|x| {
-            pointer = (pointer + step_length) % x + 1;
-            pointer == 1
+    let step_length = step_length.trim().parse::<usize>().ok()?;
+    Spinlock::new(step_length, 49999999).value_at(1)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Spinlock {
+    step_length: usize,
+    insertions: u32,
+}
+
+impl Spinlock {
+    pub fn new(step_length: usize, insertions: u32) -> Spinlock {
+        Spinlock {
+            step_length,
+            insertions,
+        }
+    }
+
+    pub fn buffer_len(&self) -> usize {
+        self.insertions as usize + 1
+    }
+
+    pub fn value_after(&self, value: u32) -> Option<u32> {
+        let position = self.position_of(value)?;
+        self.value_at((position + 1) % self.buffer_len())
+    }
+
+    // Every insertion lands after index 0, so an index near the front only
+    // changes when something is inserted at or before it.
+    pub fn value_at(&self, index: usize) -> Option<u32> {
+        if index >= self.buffer_len() {
+            return None;
+        }
+        if index >= TRACKED_PREFIX {
+            return self.simulate().get(index);
+        }
+        let mut prefix = vec![0];
+        for (value, position) in self.positions() {
+            if position <= index {
+                prefix.insert(position, value);
+                prefix.truncate(index + 1);
+            }
+        }
+        prefix.get(index).cloned()
+    }
+
+    pub fn position_of(&self, value: u32) -> Option<usize> {
+        if value > self.insertions {
+            return None;
+        }
+        if value == 0 {
+            return Some(0);
+        }
+        let mut index = 0;
+        for (inserted, position) in self.positions() {
+            if inserted == value {
+                index = position;
+            } else if inserted > value && position <= index {
+                index += 1;
+            }
+        }
+        Some(index)
+    }
+
+    pub fn buffer(&self) -> Vec<u32> {
+        self.simulate().chunks.concat()
+    }
+
+    fn positions(&self) -> impl Iterator<Item = (u32, usize)> {
+        let step_length = self.step_length;
+        let mut pointer = 0;
+        (1..=self.insertions).map(move |value| {
+            pointer = (pointer + step_length) % value as usize + 1;
+            (value, pointer)
         })
-        .last()
+    }
+
+    fn simulate(&self) -> ChunkedBuffer {
+        let mut buffer = ChunkedBuffer {
+            chunks: vec![vec![0]],
+        };
+        let (mut chunk, mut chunk_start) = (0, 0);
+        for (value, position) in self.positions() {
+            if position < chunk_start {
+                chunk = 0;
+                chunk_start = 0;
+            }
+            while position > chunk_start + buffer.chunks[chunk].len() {
+                chunk_start += buffer.chunks[chunk].len();
+                chunk += 1;
+            }
+            buffer.chunks[chunk].insert(position - chunk_start, value);
+            if buffer.chunks[chunk].len() > 2 * CHUNK_SIZE {
+                let tail = buffer.chunks[chunk].split_off(CHUNK_SIZE);
+                buffer.chunks.insert(chunk + 1, tail);
+                if position - chunk_start >= CHUNK_SIZE {
+                    chunk_start += CHUNK_SIZE;
+                    chunk += 1;
+                }
+            }
+        }
+        buffer
+    }
+}
+
+// The pointer only moves forward between wraps, so a chunk cursor rarely has
+// to look further than the chunk it is already in.
+struct ChunkedBuffer {
+    chunks: Vec<Vec<u32>>,
+}
+
+impl ChunkedBuffer {
+    fn get(&self, index: usize) -> Option<u32> {
+        let mut index = index;
+        for chunk in self.chunks.iter() {
+            if index < chunk.len() {
+                return Some(chunk[index]);
+            }
+            index -= chunk.len();
+        }
+        None
+    }
 }
 
 #[test]
 fn test_examples() {
     assert_eq!(Some(638), spinlock("3"));
 }
+
+#[test]
+fn test_spinlock() {
+    let spinlock = Spinlock::new(3, 9);
+    assert_eq!(vec![0, 9, 5, 7, 2, 4, 3, 8, 6, 1], spinlock.buffer());
+    assert_eq!(Some(5), spinlock.value_after(9));
+    assert_eq!(Some(0), spinlock.value_after(1));
+    assert_eq!(Some(2), spinlock.position_of(5));
+    assert_eq!(Some(7), spinlock.value_at(3));
+    assert_eq!(None, spinlock.value_at(10));
+    assert_eq!(None, spinlock.position_of(10));
+    assert_eq!(None, spinlock.value_after(10));
+    assert_eq!(10, spinlock.buffer_len());
+    assert_eq!(vec![0], Spinlock::new(3, 0).buffer());
+    assert_eq!(Some(0), Spinlock::new(3, 0).value_after(0));
+    assert_eq!(Some(0), Spinlock::new(3, u32::MAX).position_of(0));
+    assert_eq!(
+        Some((2, 1)),
+        Spinlock::new(3, u32::MAX).positions().nth(1)
+    );
+
+    let spinlock = Spinlock::new(348, 3000);
+    let buffer = spinlock.buffer();
+    assert_eq!(3001, buffer.len());
+    for (index, value) in buffer.iter().enumerate().step_by(7) {
+        assert_eq!(Some(*value), spinlock.value_at(index));
+        assert_eq!(Some(index), spinlock.position_of(*value));
+        assert_eq!(
+            Some(buffer[(index + 1) % buffer.len()]),
+            spinlock.value_after(*value)
+        );
+    }
+    let mut sorted = buffer.clone();
+    sorted.sort();
+    assert_eq!((0..3001).collect::<Vec<u32>>(), sorted);
+}
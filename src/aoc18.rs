@@ -47,7 +47,7 @@ struct ParallelProgram {
 impl ParallelProgram {
     fn new(program: Vec<Instruction>, id: i64) -> ParallelProgram {
         let mut program = ParallelProgram {
-            program: program,
+            program,
             registers: HashMap::new(),
             sent_queue: LinkedList::new(),
             program_counter: 0,
@@ -93,10 +93,7 @@ impl ParallelProgram {
         }
     }
     fn is_waiting(&self) -> bool {
-        match self.program[self.program_counter] {
-            Instruction::Rcv(_) => true,
-            _ => false,
-        }
+        matches!(self.program[self.program_counter], Instruction::Rcv(_))
     }
     fn is_locked(&self) -> bool {
         self.is_waiting() && self.sent_queue.is_empty()
@@ -116,7 +113,7 @@ struct SoundProcessor {
 impl SoundProcessor {
     fn new(program: Vec<Instruction>) -> SoundProcessor {
         SoundProcessor {
-            program: program,
+            program,
             registers: HashMap::new(),
             last_sound: 0,
             program_counter: 0,
@@ -159,9 +156,7 @@ impl SoundProcessor {
 }
 
 fn get_register_value(registers: &mut HashMap<char, i64>, register: char) -> i64 {
-    if !registers.contains_key(&register) {
-        registers.insert(register, 0);
-    }
+    registers.entry(register).or_insert(0);
     *registers.get(&register).unwrap()
 }
 
@@ -174,7 +169,7 @@ fn get_value(registers: &mut HashMap<char, i64>, operand: &Operand) -> i64 {
 
 enum Instruction {
     Snd(char),
-    AritIns(char, Operand, &'static Fn(i64, i64) -> i64),
+    AritIns(char, Operand, &'static dyn Fn(i64, i64) -> i64),
     Rcv(char),
     Jgz(Operand, Operand),
 }
@@ -191,38 +186,38 @@ impl FromStr for Instruction {
         let mut tokens = s.trim().split(' ');
         match tokens.next() {
             Some("snd") => Ok(Instruction::Snd(
-                char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                char::from_str(tokens.next().ok_or(utils::Error)?)?,
             )),
             Some("set") => {
                 Ok(Instruction::AritIns(
-                    char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                    char::from_str(tokens.next().ok_or(utils::Error)?)?,
                     Operand::from_str(tokens.next().ok_or(utils::Error)?)?,
                     &Instruction::set,
                 ))
             }
             Some("add") => {
                 Ok(Instruction::AritIns(
-                    char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                    char::from_str(tokens.next().ok_or(utils::Error)?)?,
                     Operand::from_str(tokens.next().ok_or(utils::Error)?)?,
                     &Add::<i64>::add,
                 ))
             }
             Some("mul") => {
                 Ok(Instruction::AritIns(
-                    char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                    char::from_str(tokens.next().ok_or(utils::Error)?)?,
                     Operand::from_str(tokens.next().ok_or(utils::Error)?)?,
                     &Mul::<i64>::mul,
                 ))
             }
             Some("mod") => {
                 Ok(Instruction::AritIns(
-                    char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                    char::from_str(tokens.next().ok_or(utils::Error)?)?,
                     Operand::from_str(tokens.next().ok_or(utils::Error)?)?,
                     &Rem::<i64>::rem,
                 ))
             }
             Some("rcv") => Ok(Instruction::Rcv(
-                char::from_str(&tokens.next().ok_or(utils::Error)?)?,
+                char::from_str(tokens.next().ok_or(utils::Error)?)?,
             )),
             Some("jgz") => {
                 Ok(Instruction::Jgz(
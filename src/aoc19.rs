@@ -41,7 +41,7 @@ struct Position {
 
 impl Position {
     fn new(x: usize, y: usize) -> Position {
-        Position { x: x, y: y }
+        Position { x, y }
     }
 }
 
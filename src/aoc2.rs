@@ -4,7 +4,7 @@ pub fn checksum(digits: &str) -> Option<u32> {
     let mut checksum = 0;
     let values = parse_file(digits)?;
     for row in values.iter() {
-        let (min, max) = row.iter().fold((u32::max_value(), 0), |acc, item| {
+        let (min, max) = row.iter().fold((u32::MAX, 0), |acc, item| {
             (u32::min(*item, acc.0), u32::max(*item, acc.1))
         });
         checksum += max - min;
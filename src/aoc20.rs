@@ -74,8 +74,7 @@ fn tupple_from_str(s: &str) -> Result<(i32, i32, i32), utils::Error> {
         s.split('<')
             .nth(1)
             .ok_or(utils::Error)?
-            .split('>')
-            .nth(0)
+            .split('>').next()
             .ok_or(utils::Error)?,
         ',',
     ).ok_or(utils::Error)?;
@@ -17,7 +17,7 @@ pub fn calculate_bigger_than(limit: u32) -> Option<u32> {
     }
 }
 
-fn calculate_value(position: &Position, map: &Vec<Cell>) -> u32 {
+fn calculate_value(position: &Position, map: &[Cell]) -> u32 {
     if position.is_start() {
         1
     } else {
@@ -35,7 +35,7 @@ struct Position {
 
 impl Position {
     fn new(x: i32, y: i32) -> Position {
-        Position { x: x, y: y }
+        Position { x, y }
     }
 
     fn new_from_cell(cell: u32) -> Option<Position> {
@@ -89,8 +89,8 @@ struct Cell {
 impl Cell {
     fn new(position: Position, value: u32) -> Cell {
         Cell {
-            position: position,
-            value: value,
+            position,
+            value,
         }
     }
 }
@@ -138,19 +138,19 @@ fn test_calculate_position() {
 
 #[test]
 fn test_calculate_value() {
-    assert_eq!(1, calculate_value(&Position::new(0, 0), &vec![]));
+    assert_eq!(1, calculate_value(&Position::new(0, 0), &[]));
     assert_eq!(
         1,
         calculate_value(
             &Position::new(0, 1),
-            &vec![Cell::new(Position::new_from_cell(1).unwrap(), 1)],
+            &[Cell::new(Position::new_from_cell(1).unwrap(), 1)],
         )
     );
     assert_eq!(
         2,
         calculate_value(
             &Position::new(0, 1),
-            &vec![
+            &[
                 Cell::new(Position::new_from_cell(1).unwrap(), 1),
                 Cell::new(Position::new_from_cell(2).unwrap(), 1),
             ],
@@ -18,9 +18,9 @@ pub fn detect_cycle(input: &str) -> Option<(u32, usize)> {
 
 }
 
-fn redistribute(banks: &Vec<u32>) -> Vec<u32> {
+fn redistribute(banks: &[u32]) -> Vec<u32> {
     if banks.len() <= 1 {
-        return banks.clone();
+        return banks.to_vec();
     }
     let (offset, max) = banks.iter().enumerate().fold(
         (banks.len(), 0),
@@ -30,7 +30,7 @@ fn redistribute(banks: &Vec<u32>) -> Vec<u32> {
             acc
         },
     );
-    let mut redistributed = banks.clone();
+    let mut redistributed = banks.to_vec();
     redistributed[offset] = 0;
     for i in 0..(max as usize) {
         let index = (i + offset + 1) % banks.len();
@@ -54,10 +54,10 @@ fn test_examples() {
 
 #[test]
 fn test_redistribute() {
-    assert_eq!(vec![2, 4, 1, 2], redistribute(&vec![0, 2, 7, 0]));
-    assert_eq!(vec![3, 1, 2, 3], redistribute(&vec![2, 4, 1, 2]));
-    assert_eq!(vec![0, 2, 3, 4], redistribute(&vec![3, 1, 2, 3]));
-    assert_eq!(vec![1, 3, 4, 1], redistribute(&vec![0, 2, 3, 4]));
-    assert_eq!(vec![1, 3, 0, 1], redistribute(&vec![0, 2, 3, 0]));
-    assert_eq!(vec![0, 0, 3, 1], redistribute(&vec![0, 2, 2, 0]));
+    assert_eq!(vec![2, 4, 1, 2], redistribute(&[0, 2, 7, 0]));
+    assert_eq!(vec![3, 1, 2, 3], redistribute(&[2, 4, 1, 2]));
+    assert_eq!(vec![0, 2, 3, 4], redistribute(&[3, 1, 2, 3]));
+    assert_eq!(vec![1, 3, 4, 1], redistribute(&[0, 2, 3, 4]));
+    assert_eq!(vec![1, 3, 0, 1], redistribute(&[0, 2, 3, 0]));
+    assert_eq!(vec![0, 0, 3, 1], redistribute(&[0, 2, 2, 0]));
 }
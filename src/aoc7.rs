@@ -1,42 +1,71 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 
 type ProgramRecord = (String, u32, Vec<String>);
 
 pub fn find_bottom_program(programs: &str) -> Option<String> {
-    Some(find_bottom_program_impl(programs)?.name)
+    Some(String::from(parse_tower(programs).ok()?.root()))
 }
 
 pub fn correct_weight(programs: &str) -> Option<u32> {
-    let bottom_program = find_bottom_program_impl(programs)?;
-    Some(bottom_program.balance()?)
+    match parse_tower(programs).ok()?.imbalances().into_iter().next()?.correction {
+        Correction::Weight { corrected_weight, .. } => Some(corrected_weight),
+        _ => None,
+    }
 }
 
-fn find_bottom_program_impl(programs: &str) -> Option<Program> {
-    let mut records = HashMap::new();
-    for line in programs.lines() {
-        let record = parse_record(&line)?;
-        records.insert(record.0.clone(), record);
+pub fn parse_tower(programs: &str) -> Result<Tower, TowerError> {
+    let mut records = vec![];
+    for (index, line) in programs.lines().enumerate() {
+        records.push(parse_record(line).ok_or(TowerError::InvalidRecord(index + 1))?);
     }
-    let records = records;
-    let mut programs = vec![];
-    for (_name, record) in records.iter() {
-        programs.push(Program::from(&record, &records)?);
+    Tower::from(records)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TowerError {
+    Empty,
+    InvalidRecord(usize),
+    DuplicateName(String),
+    MissingChild(String, String),
+    MultipleParents(String),
+    Cycle(Vec<String>),
+    MultipleRoots(Vec<String>),
+}
 
+impl fmt::Display for TowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TowerError::Empty => write!(f, "tower has no programs"),
+            TowerError::InvalidRecord(line) => write!(f, "line {}: invalid program record", line),
+            TowerError::DuplicateName(ref name) => {
+                write!(f, "program '{}' is defined more than once", name)
+            }
+            TowerError::MissingChild(ref program, ref child) => {
+                write!(f, "program '{}' holds unknown program '{}'", program, child)
+            }
+            TowerError::MultipleParents(ref name) => {
+                write!(f, "program '{}' is held by more than one program", name)
+            }
+            TowerError::Cycle(ref path) => {
+                write!(f, "programs form a cycle: {} -> {}", path.join(" -> "), path[0])
+            }
+            TowerError::MultipleRoots(ref roots) => {
+                write!(f, "tower has more than one bottom: {}", roots.join(", "))
+            }
+        }
     }
-    let max = programs.iter().max_by(
-        |&x, &y| x.tower_size().cmp(&y.tower_size()),
-    )?;
-    Some(max.clone())
 }
 
 fn parse_record(line: &str) -> Option<ProgramRecord> {
     let mut tokens = line.split(' ');
     let name = String::from(tokens.next()?);
-    let weight =
-        match u32::from_str_radix(tokens.next()?.trim_matches(|c| c == '(' || c == ')'), 10) {
-            Ok(x) => Some(x),
-            _ => None,
-        }?;
+    let weight = tokens
+        .next()?
+        .trim_matches(|c| c == '(' || c == ')')
+        .parse::<u32>()
+        .ok()?;
     let children = if tokens.next().is_some() {
         tokens.map(|x| String::from(x.trim_matches(','))).collect()
     } else {
@@ -45,95 +74,316 @@ fn parse_record(line: &str) -> Option<ProgramRecord> {
     Some((name, weight, children))
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-struct Program {
-    name: String,
-    weight: u32,
-    children: Vec<Program>,
+pub struct Tower {
+    names: Vec<String>,
+    weights: Vec<u32>,
+    children: Vec<Vec<usize>>,
+    indices: HashMap<String, usize>,
+    root: usize,
+    order: Vec<usize>,
+    tower_weights: Vec<u64>,
 }
 
-impl Program {
-    fn new(name: String, weight: u32, children: Vec<Program>) -> Program {
-        Program {
-            name: name,
-            weight: weight,
-            children: children,
+impl Tower {
+    fn from(records: Vec<ProgramRecord>) -> Result<Tower, TowerError> {
+        if records.is_empty() {
+            return Err(TowerError::Empty);
+        }
+        let mut indices = HashMap::with_capacity(records.len());
+        for (index, record) in records.iter().enumerate() {
+            if indices.insert(record.0.clone(), index).is_some() {
+                return Err(TowerError::DuplicateName(record.0.clone()));
+            }
+        }
+        let mut names = Vec::with_capacity(records.len());
+        let mut weights = Vec::with_capacity(records.len());
+        let mut children = Vec::with_capacity(records.len());
+        let mut parents = vec![None; records.len()];
+        for (index, (name, weight, record_children)) in records.into_iter().enumerate() {
+            let mut program_children = Vec::with_capacity(record_children.len());
+            for child in record_children {
+                let child_index = match indices.get(&child) {
+                    Some(child_index) => *child_index,
+                    None => return Err(TowerError::MissingChild(name, child)),
+                };
+                if parents[child_index].is_some() {
+                    return Err(TowerError::MultipleParents(child));
+                }
+                parents[child_index] = Some(index);
+                program_children.push(child_index);
+            }
+            names.push(name);
+            weights.push(weight);
+            children.push(program_children);
+        }
+        let roots = (0..names.len())
+            .filter(|x| parents[*x].is_none())
+            .collect::<Vec<usize>>();
+        let mut order = vec![];
+        for root in roots.iter() {
+            order.append(&mut post_order(*root, &children));
+        }
+        if order.len() != names.len() {
+            let mut reached = vec![false; names.len()];
+            order.iter().for_each(|x| reached[*x] = true);
+            let start = (0..names.len()).find(|x| !reached[*x]).unwrap();
+            return Err(TowerError::Cycle(
+                find_cycle(start, &parents)
+                    .into_iter()
+                    .map(|x| names[x].clone())
+                    .collect(),
+            ));
+        }
+        if roots.len() > 1 {
+            return Err(TowerError::MultipleRoots(
+                roots.into_iter().map(|x| names[x].clone()).collect(),
+            ));
         }
+        let root = roots[0];
+        let mut tower_weights = weights.iter().map(|x| *x as u64).collect::<Vec<u64>>();
+        for program in order.iter() {
+            tower_weights[*program] += children[*program]
+                .iter()
+                .fold(0, |acc, x| acc + tower_weights[*x]);
+        }
+        Ok(Tower {
+            names,
+            weights,
+            children,
+            indices,
+            root,
+            order,
+            tower_weights,
+        })
     }
 
-    fn from(record: &ProgramRecord, records: &HashMap<String, ProgramRecord>) -> Option<Program> {
-        let mut children = vec![];
-        for child in record.2.iter() {
-            children.push(Program::from(records.get(child)?, records)?);
-        }
-        Some(Program::new(record.0.clone(), record.1, children))
+    pub fn root(&self) -> &str {
+        &self.names[self.root]
+    }
+
+    pub fn weight(&self, name: &str) -> Option<u32> {
+        Some(self.weights[*self.indices.get(name)?])
     }
 
-    fn tower_size(&self) -> u32 {
-        1 + self.children.iter().fold(0, |acc, x| acc + x.tower_size())
+    pub fn tower_weight(&self, name: &str) -> Option<u64> {
+        Some(self.tower_weights[*self.indices.get(name)?])
     }
 
-    fn tower_weight(&self) -> u32 {
-        self.weight +
-            self.children.iter().fold(
-                0,
-                |acc, x| acc + x.tower_weight(),
-            )
+    pub fn tower_weights(&self) -> HashMap<String, u64> {
+        self.names
+            .iter()
+            .cloned()
+            .zip(self.tower_weights.iter().cloned())
+            .collect()
     }
 
-    fn balance(&self) -> Option<u32> {
-        if self.is_balanced() {
-            return None;
+    pub fn imbalances(&self) -> Vec<Imbalance> {
+        let mut imbalances = vec![];
+        for program in self.order.iter() {
+            let children = &self.children[*program];
+            if children.iter().all(|x| {
+                self.tower_weights[*x] == self.tower_weights[children[0]]
+            })
+            {
+                continue;
+            }
+            imbalances.push(Imbalance {
+                program: self.names[*program].clone(),
+                children: children
+                    .iter()
+                    .map(|x| (self.names[*x].clone(), self.tower_weights[*x]))
+                    .collect(),
+                correction: self.correction(children),
+            });
         }
-        for child in self.children.iter() {
-            if let Some(weight) = child.balance() {
-                return Some(weight);
+        imbalances
+    }
+
+    pub fn to_dot(&self) -> String {
+        let imbalances = self.imbalances();
+        let mut dot = String::from("digraph tower {\n");
+        for (program, name) in self.names.iter().enumerate() {
+            dot.push_str(&format!(
+                "    {} [label={}",
+                quote(name),
+                quote(&format!(
+                    "{}\n{} ({})",
+                    name,
+                    self.weights[program],
+                    self.tower_weights[program]
+                )),
+            ));
+            if imbalances.iter().any(|x| x.program == *name) {
+                dot.push_str(", color=red");
+            }
+            if imbalances.iter().any(|x| match x.correction {
+                Correction::Weight { ref program, .. } => program == name,
+                Correction::Unfixable(ref program) => program == name,
+                Correction::Ambiguous => false,
+            })
+            {
+                dot.push_str(", style=filled, fillcolor=orange");
             }
+            dot.push_str("];\n");
         }
-        Some(self.balance_children())
+        for (program, children) in self.children.iter().enumerate() {
+            for child in children.iter() {
+                dot.push_str(&format!(
+                    "    {} -> {};\n",
+                    quote(&self.names[program]),
+                    quote(&self.names[*child])
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
     }
 
-    fn is_balanced(&self) -> bool {
-        let weights = self.children
-            .iter()
-            .map(|x| x.tower_weight())
-            .collect::<Vec<u32>>();
-        if self.children.len() == 0 {
-            true
-        } else {
-            let first = weights[0];
-            for weight in weights.iter() {
-                if *weight != first {
-                    return false;
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let mut stack = vec![(self.root, 0)];
+        self.open_json(self.root, &mut json);
+        while let Some(&mut (program, ref mut next)) = stack.last_mut() {
+            match self.children[program].get(*next) {
+                Some(&child) => {
+                    if *next > 0 {
+                        json.push(',');
+                    }
+                    *next += 1;
+                    self.open_json(child, &mut json);
+                    stack.push((child, 0));
+                }
+                None => {
+                    json.push_str("]}");
+                    stack.pop();
                 }
             }
-            true
         }
+        json
     }
 
-    fn balance_children(&self) -> u32 {
-        let weights = self.children
+    fn open_json(&self, program: usize, json: &mut String) {
+        let children = &self.children[program];
+        json.push_str(&format!(
+            "{{\"name\":{},\"weight\":{},\"tower_weight\":{},\"balanced\":{},\"children\":[",
+            quote(&self.names[program]),
+            self.weights[program],
+            self.tower_weights[program],
+            children.iter().all(|x| {
+                self.tower_weights[*x] == self.tower_weights[children[0]]
+            })
+        ));
+    }
+
+    fn correction(&self, children: &[usize]) -> Correction {
+        let count = |weight: u64| {
+            children
+                .iter()
+                .filter(|x| self.tower_weights[**x] == weight)
+                .count()
+        };
+        let expected = match children
             .iter()
-            .map(|x| x.tower_weight())
-            .collect::<Vec<u32>>();
-        let mut weight = weights[0];
-        let different = weights
+            .map(|x| self.tower_weights[*x])
+            .find(|x| count(*x) > 1) {
+            Some(expected) => expected,
+            None => return Correction::Ambiguous,
+        };
+        let odd = children
             .iter()
-            .enumerate()
-            .filter(|&(_, x)| *x != weight)
+            .filter(|x| self.tower_weights[**x] != expected)
             .collect::<Vec<_>>();
-        let unbalanced = if different.len() > 1 {
-            weight = weights[1];
-            &self.children[0]
-        } else {
-            &self.children[different[0].0]
-        };
-        weight -
-            unbalanced.children.iter().fold(
-                0,
-                |acc, x| acc + x.tower_weight(),
-            )
+        if odd.len() != 1 || count(expected) + 1 != children.len() {
+            return Correction::Ambiguous;
+        }
+        let unbalanced = *odd[0];
+        let children_weight = self.tower_weights[unbalanced] - self.weights[unbalanced] as u64;
+        match expected
+            .checked_sub(children_weight)
+            .and_then(|x| u32::try_from(x).ok()) {
+            Some(corrected_weight) => Correction::Weight {
+                program: self.names[unbalanced].clone(),
+                weight: self.weights[unbalanced],
+                corrected_weight,
+            },
+            None => Correction::Unfixable(self.names[unbalanced].clone()),
+        }
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn find_cycle(start: usize, parents: &[Option<usize>]) -> Vec<usize> {
+    let mut seen = vec![false; parents.len()];
+    let mut program = start;
+    while !seen[program] {
+        seen[program] = true;
+        program = parents[program].unwrap();
+    }
+    let mut cycle = vec![program];
+    let mut parent = parents[program].unwrap();
+    while parent != program {
+        cycle.push(parent);
+        parent = parents[parent].unwrap();
+    }
+    cycle.reverse();
+    let first = (0..cycle.len()).min_by_key(|x| cycle[*x]).unwrap();
+    cycle.rotate_left(first);
+    cycle
+}
+
+fn post_order(root: usize, children: &[Vec<usize>]) -> Vec<usize> {
+    let mut order = Vec::with_capacity(children.len());
+    let mut visited = vec![false; children.len()];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some(&mut (program, ref mut next)) = stack.last_mut() {
+        match children[program].get(*next) {
+            Some(&child) => {
+                *next += 1;
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push((child, 0));
+                }
+            }
+            None => {
+                order.push(program);
+                stack.pop();
+            }
+        }
     }
+    order
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Imbalance {
+    pub program: String,
+    pub children: Vec<(String, u64)>,
+    pub correction: Correction,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Correction {
+    Weight {
+        program: String,
+        weight: u32,
+        corrected_weight: u32,
+    },
+    Ambiguous,
+    Unfixable(String),
 }
 
 #[test]
@@ -152,6 +402,146 @@ fn test_examples() {
     );
 }
 
+#[test]
+fn test_tower() {
+    let tower = parse_tower(
+        "pbga (66)\nxhth (57)\nebii (61)\nhavc (66)\nktlj (57)\nfwft (72) -> ktlj, cntj, xhth\nqoyq (66)\npadx (45) -> pbga, havc, qoyq\ntknk (41) -> ugml, padx, fwft\njptl (61)\nugml (68) -> gyxo, ebii, jptl\ngyxo (61)\ncntj (57)",
+    ).unwrap();
+    assert_eq!("tknk", tower.root());
+    assert_eq!(Some(68), tower.weight("ugml"));
+    assert_eq!(Some(251), tower.tower_weight("ugml"));
+    assert_eq!(None, tower.tower_weight("xxxx"));
+    assert_eq!(13, tower.tower_weights().len());
+    assert_eq!(Some(&778), tower.tower_weights().get("tknk"));
+    assert_eq!(
+        vec![
+            Imbalance {
+                program: String::from("tknk"),
+                children: vec![
+                    (String::from("ugml"), 251),
+                    (String::from("padx"), 243),
+                    (String::from("fwft"), 243),
+                ],
+                correction: Correction::Weight {
+                    program: String::from("ugml"),
+                    weight: 68,
+                    corrected_weight: 60,
+                },
+            },
+        ],
+        tower.imbalances()
+    );
+}
+
+#[test]
+fn test_ambiguous_imbalance() {
+    let tower = parse_tower("root (1) -> a, b\na (5)\nb (7)").unwrap();
+    assert_eq!(
+        vec![
+            Imbalance {
+                program: String::from("root"),
+                children: vec![(String::from("a"), 5), (String::from("b"), 7)],
+                correction: Correction::Ambiguous,
+            },
+        ],
+        tower.imbalances()
+    );
+    assert_eq!(None, correct_weight("root (1) -> a, b\na (5)\nb (7)"));
+    assert_eq!(
+        Correction::Unfixable(String::from("c")),
+        parse_tower("root (1) -> a, b, c\na (5)\nb (5)\nc (1) -> d\nd (9)")
+            .unwrap()
+            .imbalances()[0]
+            .correction
+    );
+}
+
+#[test]
+fn test_deep_tower() {
+    let depth = 200000;
+    let programs = (0..depth)
+        .map(|x| if x + 1 < depth {
+            format!("p{} (1) -> p{}", x, x + 1)
+        } else {
+            format!("p{} (1)", x)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let tower = parse_tower(&programs).unwrap();
+    assert_eq!("p0", tower.root());
+    assert_eq!(Some(depth), tower.tower_weight("p0"));
+    assert!(tower.imbalances().is_empty());
+}
+
+#[test]
+fn test_heavy_tower() {
+    let tower = parse_tower("a (4000000000) -> b\nb (4000000000)").unwrap();
+    assert_eq!(Some(8000000000), tower.tower_weight("a"));
+    assert_eq!(
+        Some(4000000001),
+        correct_weight("a (1) -> b, c, d\nb (4000000000) -> e\nc (4000000000) -> f\nd (4000000000) -> g\ne (294967295)\nf (294967295)\ng (294967294)")
+    );
+    let tower = parse_tower("a (1) -> b, c, d\nb (4294967295) -> e\nc (4294967295) -> f\nd (1) -> g\ne (4294967295)\nf (4294967295)\ng (1)")
+        .unwrap();
+    assert_eq!(
+        Correction::Unfixable(String::from("d")),
+        tower.imbalances()[0].correction
+    );
+}
+
+#[test]
+fn test_tower_errors() {
+    assert_eq!(Some(TowerError::Empty), parse_tower("").err());
+    assert_eq!(
+        Some(TowerError::InvalidRecord(2)),
+        parse_tower("a (1) -> b\nb (x)").err()
+    );
+    assert_eq!(
+        Some(TowerError::DuplicateName(String::from("b"))),
+        parse_tower("a (1) -> b\nb (1)\nb (2)").err()
+    );
+    assert_eq!(
+        Some(TowerError::MissingChild(String::from("a"), String::from("c"))),
+        parse_tower("a (1) -> b, c\nb (1)").err()
+    );
+    assert_eq!(
+        Some(TowerError::MultipleParents(String::from("c"))),
+        parse_tower("a (1) -> b, c\nb (1) -> c\nc (1)").err()
+    );
+    assert_eq!(
+        Some(TowerError::Cycle(
+            vec![String::from("b"), String::from("c"), String::from("d")],
+        )),
+        parse_tower("a (1)\nb (1) -> c\nc (1) -> d\nd (1) -> b").err()
+    );
+    assert_eq!(
+        Some(TowerError::Cycle(vec![String::from("a"), String::from("b")])),
+        parse_tower("a (1) -> b\nb (1) -> a").err()
+    );
+    assert_eq!(
+        Some(TowerError::MultipleRoots(vec![String::from("a"), String::from("c")])),
+        parse_tower("a (1) -> b\nb (1)\nc (1)").err()
+    );
+    assert_eq!(
+        "programs form a cycle: a -> b -> a",
+        format!("{}", parse_tower("a (1) -> b\nb (1) -> a").err().unwrap())
+    );
+}
+
+#[test]
+fn test_export() {
+    let tower = parse_tower("root (1) -> a, b, c\na (5)\nb (5)\nc (2) -> d\nd (4)").unwrap();
+    assert_eq!(
+        "digraph tower {\n    \"root\" [label=\"root\\n1 (17)\", color=red];\n    \"a\" [label=\"a\\n5 (5)\"];\n    \"b\" [label=\"b\\n5 (5)\"];\n    \"c\" [label=\"c\\n2 (6)\", style=filled, fillcolor=orange];\n    \"d\" [label=\"d\\n4 (4)\"];\n    \"root\" -> \"a\";\n    \"root\" -> \"b\";\n    \"root\" -> \"c\";\n    \"c\" -> \"d\";\n}\n",
+        tower.to_dot()
+    );
+    assert_eq!(
+        "{\"name\":\"root\",\"weight\":1,\"tower_weight\":17,\"balanced\":false,\"children\":[{\"name\":\"a\",\"weight\":5,\"tower_weight\":5,\"balanced\":true,\"children\":[]},{\"name\":\"b\",\"weight\":5,\"tower_weight\":5,\"balanced\":true,\"children\":[]},{\"name\":\"c\",\"weight\":2,\"tower_weight\":6,\"balanced\":true,\"children\":[{\"name\":\"d\",\"weight\":4,\"tower_weight\":4,\"balanced\":true,\"children\":[]}]}]}",
+        tower.to_json()
+    );
+    assert_eq!("\"a\\\"b\\\\c\"", quote("a\"b\\c"));
+}
+
 #[test]
 fn test_parse_record() {
     assert_eq!(None, parse_record(""));
@@ -1,6 +1,6 @@
 use std::cmp;
-use std::collections::HashMap;
-use std::convert::AsRef;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::str::FromStr;
 use utils;
 
@@ -13,25 +13,47 @@ pub fn find_max_total(program: &str) -> Option<i32> {
 }
 
 fn find_max_impl(program: &str, end_max: bool) -> Option<i32> {
-    let instructions = parse_program(program)?;
-    let mut registers = HashMap::new();
-    let mut max = i32::min_value();
-    for instruction in instructions.iter() {
-        instruction.process(&mut registers);
+    let instructions = program
+        .lines()
+        .map(parse_legacy_instruction)
+        .collect::<Option<Vec<Instruction>>>()?;
+    let mut interpreter = Interpreter::new(&instructions).ok()?;
+    let mut max = i32::MIN;
+    while interpreter.step().ok()? {
         if !end_max {
-            max = cmp::max(
-                max,
-                *registers.iter().max_by(|&(_, l), &(_, r)| l.cmp(r))?.1,
-            );
+            max = cmp::max(max, *interpreter.registers().values().max()?);
         }
     }
     if end_max {
-        max = *registers.iter().max_by(|&(_, l), &(_, r)| l.cmp(r))?.1;
+        max = *interpreter.registers().values().max()?;
     }
     Some(max)
 }
 
-fn parse_program(program: &str) -> Option<Vec<Instruction>> {
+// The part one format as first parsed: the word before the condition is
+// skipped, register names are taken as written and trailing tokens ignored.
+fn parse_legacy_instruction(line: &str) -> Option<Instruction> {
+    let mut tokens = line.split(' ');
+    let register = tokens.next()?;
+    let operation = match tokens.next()? {
+        "inc" => Operation::Inc,
+        "dec" => Operation::Dec,
+        _ => return None,
+    };
+    let value = tokens.next()?.parse().ok()?;
+    tokens.next()?;
+    let condition_register = tokens.next()?;
+    let compare = Compare::from_str(tokens.next()?).ok()?;
+    let condition_value = tokens.next()?.parse().ok()?;
+    Some(Instruction::new(
+        register,
+        operation,
+        Operand::Value(value),
+        Condition::new(condition_register, compare, Operand::Value(condition_value)),
+    ))
+}
+
+pub fn parse_program<T: Integer>(program: &str) -> Option<Vec<Instruction<T>>> {
     let mut instructions = vec![];
     for line in program.lines() {
         instructions.push(Instruction::from_str(line).ok()?);
@@ -39,17 +61,308 @@ fn parse_program(program: &str) -> Option<Vec<Instruction>> {
     Some(instructions)
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ExecutionError {
+    UnknownLabel(String),
+    DuplicateLabel(String),
+    StepLimitExceeded(u64),
+    Overflow(usize),
+}
+
+pub trait Integer: Copy + Ord + Default + FromStr + fmt::Display + fmt::Debug {
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn to_i128(self) -> i128;
+}
+
+macro_rules! impl_integer {
+    ($($integer:ident),*) => {
+        $(
+            impl Integer for $integer {
+                fn checked_add(self, other: $integer) -> Option<$integer> {
+                    $integer::checked_add(self, other)
+                }
+
+                fn checked_sub(self, other: $integer) -> Option<$integer> {
+                    $integer::checked_sub(self, other)
+                }
+
+                fn checked_mul(self, other: $integer) -> Option<$integer> {
+                    $integer::checked_mul(self, other)
+                }
+
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i32, i64, i128);
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Watchpoint<T = i32> {
+    Changed(String),
+    Exceeds(T),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RegisterChange<T = i32> {
+    pub step: u64,
+    pub line: usize,
+    pub register: String,
+    pub value: T,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WatchEvent<T = i32> {
+    pub watchpoint: Watchpoint<T>,
+    pub change: RegisterChange<T>,
+}
+
+pub struct Interpreter<'a, T: 'a + Integer = i32> {
+    program: &'a [Instruction<T>],
+    labels: HashMap<&'a str, usize>,
+    registers: HashMap<String, T>,
+    program_counter: usize,
+    steps: u64,
+    history: Option<Vec<RegisterChange<T>>>,
+    watchpoints: Vec<Watchpoint<T>>,
+    events: Vec<WatchEvent<T>>,
+    stopped: bool,
+}
+
+impl<'a, T: Integer> Interpreter<'a, T> {
+    pub fn new(program: &'a [Instruction<T>]) -> Result<Interpreter<'a, T>, ExecutionError> {
+        Ok(Interpreter {
+            program,
+            labels: resolve_labels(program)?,
+            registers: HashMap::new(),
+            program_counter: 0,
+            steps: 0,
+            history: None,
+            watchpoints: vec![],
+            events: vec![],
+            stopped: false,
+        })
+    }
+
+    pub fn registers(&self) -> &HashMap<String, T> {
+        &self.registers
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn record_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(vec![]);
+        }
+    }
+
+    pub fn history(&self) -> Option<&[RegisterChange<T>]> {
+        self.history.as_deref()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint<T>) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn events(&self) -> &[WatchEvent<T>] {
+        &self.events
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.program_counter >= self.program.len()
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn step(&mut self) -> Result<bool, ExecutionError> {
+        if self.is_finished() || self.is_stopped() {
+            return Ok(false);
+        }
+        let mut next = self.program_counter + 1;
+        match self.program[self.program_counter] {
+            Instruction::Modify(ref register, ref operation, ref value, ref condition) => {
+                if evaluate(condition, &self.registers) {
+                    let current = get_register_value(&self.registers, register);
+                    let value = operation
+                        .compute(current, get_value(&self.registers, value))
+                        .ok_or(ExecutionError::Overflow(self.program_counter + 1))?;
+                    self.registers.insert(register.clone(), value);
+                    if value != current {
+                        self.record_change(register, value);
+                    }
+                }
+            }
+            Instruction::Jump(ref label, ref condition) => {
+                if evaluate(condition, &self.registers) {
+                    next = self.labels[label.as_str()];
+                }
+            }
+            Instruction::Label(_) => {}
+        }
+        self.program_counter = next;
+        self.steps += 1;
+        Ok(true)
+    }
+
+    pub fn run(&mut self, step_limit: u64) -> Result<u64, ExecutionError> {
+        while !self.is_finished() && !self.is_stopped() {
+            if self.steps >= step_limit {
+                return Err(ExecutionError::StepLimitExceeded(step_limit));
+            }
+            self.step()?;
+        }
+        Ok(self.steps)
+    }
+
+    fn record_change(&mut self, register: &str, value: T) {
+        let change = RegisterChange {
+            step: self.steps + 1,
+            line: self.program_counter + 1,
+            register: String::from(register),
+            value,
+        };
+        for watchpoint in self.watchpoints.iter() {
+            let triggered = match *watchpoint {
+                Watchpoint::Changed(ref watched) => watched == register,
+                Watchpoint::Exceeds(limit) => value > limit,
+            };
+            if triggered {
+                if let Watchpoint::Exceeds(_) = *watchpoint {
+                    self.stopped = true;
+                }
+                self.events.push(WatchEvent {
+                    watchpoint: watchpoint.clone(),
+                    change: change.clone(),
+                });
+            }
+        }
+        if let Some(ref mut history) = self.history {
+            history.push(change);
+        }
+    }
+}
+
+pub fn changes_to_csv<T: Integer>(changes: &[RegisterChange<T>]) -> String {
+    changes.iter().fold(
+        String::from("step,line,register,value\n"),
+        |mut acc, x| {
+            acc.push_str(&format!("{},{},{},{}\n", x.step, x.line, x.register, x.value));
+            acc
+        },
+    )
+}
+
+pub fn timeline_to_csv<T: Integer>(changes: &[RegisterChange<T>]) -> String {
+    let mut registers = changes
+        .iter()
+        .map(|x| x.register.as_str())
+        .collect::<Vec<&str>>();
+    registers.sort();
+    registers.dedup();
+    let mut values = vec![T::default(); registers.len()];
+    let mut csv = registers.iter().fold(String::from("step,line"), |acc, x| acc + "," + x);
+    csv.push('\n');
+    for change in changes.iter() {
+        let register = registers.binary_search(&change.register.as_str()).unwrap();
+        values[register] = change.value;
+        csv.push_str(&values.iter().fold(
+            format!("{},{}", change.step, change.line),
+            |acc, x| format!("{},{}", acc, x),
+        ));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn resolve_labels<T>(program: &[Instruction<T>]) -> Result<HashMap<&str, usize>, ExecutionError> {
+    let mut labels = HashMap::new();
+    for (index, instruction) in program.iter().enumerate() {
+        if let Instruction::Label(ref label) = *instruction {
+            if labels.insert(label.as_str(), index).is_some() {
+                return Err(ExecutionError::DuplicateLabel(label.clone()));
+            }
+        }
+    }
+    for instruction in program.iter() {
+        if let Instruction::Jump(ref label, _) = *instruction {
+            if !labels.contains_key(label.as_str()) {
+                return Err(ExecutionError::UnknownLabel(label.clone()));
+            }
+        }
+    }
+    Ok(labels)
+}
+
+fn get_register_value<T: Integer>(registers: &HashMap<String, T>, register: &str) -> T {
+    registers.get(register).cloned().unwrap_or_default()
+}
+
+fn get_value<T: Integer>(registers: &HashMap<String, T>, operand: &Operand<T>) -> T {
+    match *operand {
+        Operand::Register(ref register) => get_register_value(registers, register),
+        Operand::Value(value) => value,
+    }
+}
+
+fn evaluate<T: Integer>(condition: &Option<Condition<T>>, registers: &HashMap<String, T>) -> bool {
+    match *condition {
+        Some(ref condition) => condition.evaluate(registers),
+        None => true,
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
+pub enum Operand<T = i32> {
+    Register(String),
+    Value(T),
+}
+
+impl<T: Integer> FromStr for Operand<T> {
+    type Err = utils::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(value) = s.parse() {
+            Ok(Operand::Value(value))
+        } else if is_name(s) {
+            Ok(Operand::Register(String::from(s)))
+        } else {
+            Err(utils::Error)
+        }
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') &&
+        s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-enum Operation {
+pub enum Operation {
     Inc,
     Dec,
+    Mul,
+    Set,
 }
 
 impl Operation {
-    fn compute(&self, left: i32, right: i32) -> i32 {
+    fn compute<T: Integer>(&self, left: T, right: T) -> Option<T> {
         match *self {
-            Operation::Inc => left + right,
-            Operation::Dec => left - right,
+            Operation::Inc => left.checked_add(right),
+            Operation::Dec => left.checked_sub(right),
+            Operation::Mul => left.checked_mul(right),
+            Operation::Set => Some(right),
         }
     }
 }
@@ -57,16 +370,18 @@ impl Operation {
 impl FromStr for Operation {
     type Err = utils::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.as_ref() {
+        match s {
             "inc" => Ok(Operation::Inc),
             "dec" => Ok(Operation::Dec),
+            "mul" => Ok(Operation::Mul),
+            "set" => Ok(Operation::Set),
             _ => Err(utils::Error),
         }
     }
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-enum Compare {
+pub enum Compare {
     LT,
     HT,
     EQ,
@@ -76,7 +391,7 @@ enum Compare {
 }
 
 impl Compare {
-    fn evaluate(&self, left: i32, right: i32) -> bool {
+    fn evaluate<T: Ord>(&self, left: T, right: T) -> bool {
         match *self {
             Compare::LT => left < right,
             Compare::HT => left > right,
@@ -91,7 +406,7 @@ impl Compare {
 impl FromStr for Compare {
     type Err = utils::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.as_ref() {
+        match s {
             "<" => Ok(Compare::LT),
             ">" => Ok(Compare::HT),
             "==" => Ok(Compare::EQ),
@@ -104,75 +419,404 @@ impl FromStr for Compare {
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-struct Condition {
-    register: String,
-    compare: Compare,
-    value: i32,
+pub struct Condition<T = i32> {
+    pub register: String,
+    pub compare: Compare,
+    pub value: Operand<T>,
 }
 
-impl Condition {
-    fn new(register: &str, compare: Compare, value: i32) -> Condition {
+impl<T: Integer> Condition<T> {
+    pub fn new(register: &str, compare: Compare, value: Operand<T>) -> Condition<T> {
         Condition {
             register: String::from(register),
-            compare: compare,
-            value: value,
+            compare,
+            value,
         }
     }
 
-    fn evaluate(&self, registers: &HashMap<String, i32>) -> bool {
+    fn evaluate(&self, registers: &HashMap<String, T>) -> bool {
         self.compare.evaluate(
-            *registers.get(&self.register).unwrap_or(&0),
-            self.value,
+            get_register_value(registers, &self.register),
+            get_value(registers, &self.value),
         )
     }
 }
 
+impl<T: Integer> FromStr for Condition<T> {
+    type Err = utils::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split(' ');
+        let register = tokens.next().ok_or(utils::Error)?;
+        let compare = Compare::from_str(tokens.next().ok_or(utils::Error)?)?;
+        let value = Operand::from_str(tokens.next().ok_or(utils::Error)?)?;
+        if !is_name(register) || tokens.next().is_some() {
+            return Err(utils::Error);
+        }
+        Ok(Condition::new(register, compare, value))
+    }
+}
+
 #[derive(Debug, PartialEq, PartialOrd, Eq, Clone)]
-struct Instruction {
-    register: String,
-    operation: Operation,
-    value: i32,
-    condition: Condition,
+pub enum Instruction<T = i32> {
+    Modify(String, Operation, Operand<T>, Option<Condition<T>>),
+    Jump(String, Option<Condition<T>>),
+    Label(String),
 }
 
-impl Instruction {
-    fn new(register: &str, operation: Operation, value: i32, condition: Condition) -> Instruction {
-        Instruction {
-            register: String::from(register),
-            operation: operation,
-            value: value,
-            condition: condition,
+impl<T: Integer> Instruction<T> {
+    pub fn new(
+        register: &str,
+        operation: Operation,
+        value: Operand<T>,
+        condition: Condition<T>,
+    ) -> Instruction<T> {
+        Instruction::Modify(String::from(register), operation, value, Some(condition))
+    }
+}
+
+impl<T: Integer> FromStr for Instruction<T> {
+    type Err = utils::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.ends_with(':') {
+            let label = s.trim_end_matches(':');
+            return if is_name(label) {
+                Ok(Instruction::Label(String::from(label)))
+            } else {
+                Err(utils::Error)
+            };
+        }
+        let (action, condition) = match s.find(" if ") {
+            Some(index) => (&s[..index], Some(Condition::from_str(&s[index + 4..])?)),
+            None => (s, None),
+        };
+        let tokens = action.split(' ').collect::<Vec<&str>>();
+        match tokens.len() {
+            2 if tokens[0] == "jmp" && is_name(tokens[1]) => {
+                Ok(Instruction::Jump(String::from(tokens[1]), condition))
+            }
+            3 if is_name(tokens[0]) => Ok(Instruction::Modify(
+                String::from(tokens[0]),
+                Operation::from_str(tokens[1])?,
+                Operand::from_str(tokens[2])?,
+                condition,
+            )),
+            _ => Err(utils::Error),
         }
     }
+}
 
-    fn process(&self, registers: &mut HashMap<String, i32>) {
-        if self.condition.evaluate(registers) {
-            let value = *registers.get(&self.register).unwrap_or(&0);
-            registers.insert(
-                self.register.clone(),
-                self.operation.compute(value, self.value),
-            );
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LintKind {
+    UnusedRegister(String),
+    ReadBeforeAssignment(String),
+    NeverTrue,
+    Unreachable,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Lint {
+    pub line: usize,
+    pub kind: LintKind,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LintKind::UnusedRegister(ref register) => write!(
+                f,
+                "line {}: register '{}' is written but never read",
+                self.line,
+                register
+            ),
+            LintKind::ReadBeforeAssignment(ref register) => write!(
+                f,
+                "line {}: register '{}' is compared before it is assigned",
+                self.line,
+                register
+            ),
+            LintKind::NeverTrue => write!(f, "line {}: condition can never be true", self.line),
+            LintKind::Unreachable => write!(f, "line {}: instruction is unreachable", self.line),
         }
     }
 }
 
-impl FromStr for Instruction {
-    type Err = utils::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut tokens = s.split(' ');
-        let register = tokens.next().ok_or(utils::Error {})?;
-        let operation = Operation::from_str(tokens.next().ok_or(utils::Error {})?)?;
-        let value = i32::from_str_radix(tokens.next().ok_or(utils::Error {})?, 10)?;
-        tokens.next().ok_or(utils::Error {})?;
-        let cond_register = tokens.next().ok_or(utils::Error {})?;
-        let compare = Compare::from_str(tokens.next().ok_or(utils::Error {})?)?;
-        let cond_value = i32::from_str_radix(tokens.next().ok_or(utils::Error {})?, 10)?;
-        Ok(Instruction::new(
-            register,
-            operation,
-            value,
-            Condition::new(cond_register, compare, cond_value),
-        ))
+pub fn analyze<T: Integer>(program: &[Instruction<T>]) -> Result<Vec<Lint>, ExecutionError> {
+    let labels = resolve_labels(program)?;
+    let states = propagate_ranges(program, &labels);
+    let mut lints = vec![];
+    let mut written: Vec<(&str, usize)> = vec![];
+    let mut read: HashSet<&str> = HashSet::new();
+    for (index, instruction) in program.iter().enumerate() {
+        let line = index + 1;
+        let condition = match *instruction {
+            Instruction::Modify(ref register, _, ref value, ref condition) => {
+                if !written.iter().any(|&(x, _)| x == register) {
+                    written.push((register, line));
+                }
+                if let Operand::Register(ref register) = *value {
+                    read.insert(register);
+                }
+                condition
+            }
+            Instruction::Jump(_, ref condition) => condition,
+            Instruction::Label(_) => continue,
+        };
+        if let Some(ref condition) = *condition {
+            read.insert(&condition.register);
+            if let Operand::Register(ref register) = condition.value {
+                read.insert(register);
+            }
+        }
+        let state = match states[index] {
+            Some(ref state) => state,
+            None => {
+                lints.push(Lint {
+                    line,
+                    kind: LintKind::Unreachable,
+                });
+                continue;
+            }
+        };
+        if let Some(ref condition) = *condition {
+            let mut compared = vec![condition.register.as_str()];
+            if let Operand::Register(ref register) = condition.value {
+                if *register != condition.register {
+                    compared.push(register);
+                }
+            }
+            for register in compared {
+                if !state.assigned.contains(register) {
+                    lints.push(Lint {
+                        line,
+                        kind: LintKind::ReadBeforeAssignment(String::from(register)),
+                    });
+                }
+            }
+            if !state.can_be(condition) {
+                lints.push(Lint {
+                    line,
+                    kind: LintKind::NeverTrue,
+                });
+            }
+        }
+    }
+    for (register, line) in written {
+        if !read.contains(register) {
+            lints.push(Lint {
+                line,
+                kind: LintKind::UnusedRegister(String::from(register)),
+            });
+        }
+    }
+    lints.sort_by_key(|x| x.line);
+    Ok(lints)
+}
+
+fn propagate_ranges<T: Integer>(
+    program: &[Instruction<T>],
+    labels: &HashMap<&str, usize>,
+) -> Vec<Option<State>> {
+    let mut states: Vec<Option<State>> = vec![None; program.len()];
+    let mut visits = vec![0; program.len()];
+    let mut queue = VecDeque::new();
+    if !program.is_empty() {
+        states[0] = Some(State::new());
+        queue.push_back(0);
+    }
+    while let Some(index) = queue.pop_front() {
+        let state = states[index].clone().unwrap();
+        let mut successors = vec![];
+        match program[index] {
+            Instruction::Modify(ref register, ref operation, ref value, ref condition) => {
+                let mut next = state.clone();
+                let always = condition.as_ref().is_none_or(|x| state.always(x));
+                if condition.as_ref().is_none_or(|x| state.can_be(x)) {
+                    let range = operation.apply(state.range(register), state.value(value));
+                    let range = if always {
+                        range
+                    } else {
+                        range.join(state.range(register))
+                    };
+                    next.ranges.insert(register.clone(), range);
+                    next.assigned.insert(register.clone());
+                }
+                successors.push((index + 1, next));
+            }
+            Instruction::Jump(ref label, ref condition) => {
+                if condition.as_ref().is_none_or(|x| state.can_be(x)) {
+                    successors.push((labels[label.as_str()], state.clone()));
+                }
+                if !condition.as_ref().is_none_or(|x| state.always(x)) {
+                    successors.push((index + 1, state));
+                }
+            }
+            Instruction::Label(_) => successors.push((index + 1, state)),
+        }
+        for (successor, state) in successors {
+            if successor >= program.len() {
+                continue;
+            }
+            let merged = match states[successor] {
+                Some(ref old) => {
+                    let mut merged = old.join(&state);
+                    visits[successor] += 1;
+                    if visits[successor] > 2 {
+                        merged = old.widen(&merged);
+                    }
+                    if merged == *old {
+                        continue;
+                    }
+                    merged
+                }
+                None => state,
+            };
+            states[successor] = Some(merged);
+            queue.push_back(successor);
+        }
+    }
+    states
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Range {
+    min: i128,
+    max: i128,
+}
+
+impl Range {
+    fn new(min: i128, max: i128) -> Range {
+        Range { min, max }
+    }
+
+    fn join(&self, other: Range) -> Range {
+        Range::new(cmp::min(self.min, other.min), cmp::max(self.max, other.max))
+    }
+
+    fn widen(&self, other: Range) -> Range {
+        Range::new(
+            if other.min < self.min { i128::MIN } else { self.min },
+            if other.max > self.max { i128::MAX } else { self.max },
+        )
+    }
+}
+
+impl Operation {
+    fn apply(&self, left: Range, right: Range) -> Range {
+        match *self {
+            Operation::Inc => Range::new(
+                left.min.saturating_add(right.min),
+                left.max.saturating_add(right.max),
+            ),
+            Operation::Dec => Range::new(
+                left.min.saturating_sub(right.max),
+                left.max.saturating_sub(right.min),
+            ),
+            Operation::Mul => {
+                let products = [
+                    left.min.saturating_mul(right.min),
+                    left.min.saturating_mul(right.max),
+                    left.max.saturating_mul(right.min),
+                    left.max.saturating_mul(right.max),
+                ];
+                Range::new(
+                    *products.iter().min().unwrap(),
+                    *products.iter().max().unwrap(),
+                )
+            }
+            Operation::Set => right,
+        }
+    }
+}
+
+impl Compare {
+    fn negate(&self) -> Compare {
+        match *self {
+            Compare::LT => Compare::HE,
+            Compare::HT => Compare::LE,
+            Compare::EQ => Compare::NE,
+            Compare::NE => Compare::EQ,
+            Compare::LE => Compare::HT,
+            Compare::HE => Compare::LT,
+        }
+    }
+
+    fn can_be(&self, left: Range, right: Range) -> bool {
+        match *self {
+            Compare::LT => left.min < right.max,
+            Compare::HT => left.max > right.min,
+            Compare::EQ => left.min <= right.max && right.min <= left.max,
+            Compare::NE => !(left.min == left.max && left == right),
+            Compare::LE => left.min <= right.max,
+            Compare::HE => left.max >= right.min,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct State {
+    ranges: HashMap<String, Range>,
+    assigned: HashSet<String>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            ranges: HashMap::new(),
+            assigned: HashSet::new(),
+        }
+    }
+
+    fn range(&self, register: &str) -> Range {
+        *self.ranges.get(register).unwrap_or(&Range::new(0, 0))
+    }
+
+    fn value<T: Integer>(&self, operand: &Operand<T>) -> Range {
+        match *operand {
+            Operand::Register(ref register) => self.range(register),
+            Operand::Value(value) => Range::new(value.to_i128(), value.to_i128()),
+        }
+    }
+
+    fn can_be<T: Integer>(&self, condition: &Condition<T>) -> bool {
+        condition.compare.can_be(
+            self.range(&condition.register),
+            self.value(&condition.value),
+        )
+    }
+
+    fn always<T: Integer>(&self, condition: &Condition<T>) -> bool {
+        !condition.compare.negate().can_be(
+            self.range(&condition.register),
+            self.value(&condition.value),
+        )
+    }
+
+    fn combine<F: Fn(Range, Range) -> Range>(&self, other: &State, combine: F) -> State {
+        let mut state = self.clone();
+        for (register, range) in other.ranges.iter() {
+            state.ranges.insert(
+                register.clone(),
+                combine(self.range(register), *range),
+            );
+        }
+        for (register, range) in self.ranges.iter() {
+            if !other.ranges.contains_key(register) {
+                state.ranges.insert(
+                    register.clone(),
+                    combine(*range, Range::new(0, 0)),
+                );
+            }
+        }
+        state.assigned.extend(other.assigned.iter().cloned());
+        state
+    }
+
+    fn join(&self, other: &State) -> State {
+        self.combine(other, |x, y| x.join(y))
+    }
+
+    fn widen(&self, other: &State) -> State {
+        self.combine(other, |x, y| x.widen(y))
     }
 }
 
@@ -184,17 +828,183 @@ fn test_examples() {
             "b inc 5 if a > 1\na inc 1 if b < 5\nc dec -10 if a >= 1\nc inc -20 if c == 10",
         )
     );
+    assert_eq!(None, find_max("l:\njmp l"));
+    assert_eq!(None, find_max_total("a inc 1\nl:\njmp l if a > 0"));
+}
+
+#[test]
+fn test_legacy_format() {
+    assert_eq!(Some(1), find_max("a inc 1 if b == 0 "));
+    assert_eq!(Some(2), find_max("a-b inc 2 when 1c == 0 and more"));
+    assert_eq!(
+        Some(3),
+        find_max_total("x.y dec -3 if z < 1\nx.y dec 5 if x.y > 0")
+    );
+    assert_eq!(None, find_max("a inc 1"));
+    assert_eq!(None, find_max("a mul 2 if b == 0"));
+    assert_eq!(None, find_max("a inc b if b == 0"));
+}
+
+#[test]
+fn test_interpreter() {
+    let program = parse_program(
+        "n set 5\nf set 1\nloop:\nf mul n\nn dec 1\njmp loop if n > 0\nr set f if f == 120",
+    ).unwrap();
+    let mut interpreter = Interpreter::new(&program).unwrap();
+    assert_eq!(Ok(23), interpreter.run(100));
+    assert_eq!(Some(&120), interpreter.registers().get("r"));
+    assert_eq!(Some(&0), interpreter.registers().get("n"));
+
+    let program = parse_program("loop:\na inc 1\njmp loop").unwrap();
+    let mut interpreter = Interpreter::new(&program).unwrap();
+    assert_eq!(Err(ExecutionError::StepLimitExceeded(10)), interpreter.run(10));
+    assert_eq!(Some(&3), interpreter.registers().get("a"));
+
+    let program = parse_program::<i32>("jmp end").unwrap();
+    assert_eq!(
+        Err(ExecutionError::UnknownLabel(String::from("end"))),
+        Interpreter::new(&program).map(|_| ())
+    );
+    let program = parse_program::<i32>("a:\na:").unwrap();
+    assert_eq!(
+        Err(ExecutionError::DuplicateLabel(String::from("a"))),
+        Interpreter::new(&program).map(|_| ())
+    );
+}
+
+#[test]
+fn test_watchpoints() {
+    let program = parse_program("a inc 5\nb set a\nloop:\na inc 7\njmp loop").unwrap();
+    let mut interpreter = Interpreter::new(&program).unwrap();
+    interpreter.record_history();
+    interpreter.add_watchpoint(Watchpoint::Changed(String::from("b")));
+    interpreter.add_watchpoint(Watchpoint::Exceeds(15));
+    assert_eq!(Ok(7), interpreter.run(100));
+    assert!(interpreter.is_stopped());
+    assert_eq!(
+        vec![
+            WatchEvent {
+                watchpoint: Watchpoint::Changed(String::from("b")),
+                change: RegisterChange {
+                    step: 2,
+                    line: 2,
+                    register: String::from("b"),
+                    value: 5,
+                },
+            },
+            WatchEvent {
+                watchpoint: Watchpoint::Exceeds(15),
+                change: RegisterChange {
+                    step: 7,
+                    line: 4,
+                    register: String::from("a"),
+                    value: 19,
+                },
+            },
+        ],
+        interpreter.events()
+    );
+    assert_eq!(
+        "step,line,register,value\n1,1,a,5\n2,2,b,5\n4,4,a,12\n7,4,a,19\n",
+        changes_to_csv(interpreter.history().unwrap())
+    );
+    assert_eq!(
+        "step,line,a,b\n1,1,5,0\n2,2,5,5\n4,4,12,5\n7,4,19,5\n",
+        timeline_to_csv(interpreter.history().unwrap())
+    );
+}
+
+#[test]
+fn test_overflow() {
+    assert_eq!(None, find_max("a inc 2147483647\na inc 1"));
+    let program = parse_program::<i32>("a set 65536\na mul a").unwrap();
+    let mut interpreter = Interpreter::new(&program).unwrap();
+    assert_eq!(Err(ExecutionError::Overflow(2)), interpreter.run(10));
+    let program = parse_program::<i64>("a set 65536\na mul a").unwrap();
+    let mut interpreter = Interpreter::new(&program).unwrap();
+    assert_eq!(Ok(2), interpreter.run(10));
+    assert_eq!(Some(&4294967296), interpreter.registers().get("a"));
+    assert_eq!(None, parse_program::<i32>("a inc 4294967296"));
+}
+
+#[test]
+fn test_analyze() {
+    let program = parse_program::<i32>(
+        "a inc 5\nb inc 1 if a > 10\nc inc 1 if d == 0\njmp end\ne inc 1\nend:\nf set b if a == 5",
+    ).unwrap();
+    assert_eq!(
+        Ok(vec![
+            Lint {
+                line: 2,
+                kind: LintKind::NeverTrue,
+            },
+            Lint {
+                line: 3,
+                kind: LintKind::ReadBeforeAssignment(String::from("d")),
+            },
+            Lint {
+                line: 3,
+                kind: LintKind::UnusedRegister(String::from("c")),
+            },
+            Lint {
+                line: 5,
+                kind: LintKind::Unreachable,
+            },
+            Lint {
+                line: 5,
+                kind: LintKind::UnusedRegister(String::from("e")),
+            },
+            Lint {
+                line: 7,
+                kind: LintKind::UnusedRegister(String::from("f")),
+            },
+        ]),
+        analyze(&program)
+    );
+    assert_eq!(
+        "line 2: condition can never be true",
+        format!("{}", analyze(&program).unwrap()[0])
+    );
+
+    let program = parse_program::<i32>("loop:\na inc 1\njmp loop if a < 10\nb inc 1 if a < 0").unwrap();
+    assert_eq!(
+        Ok(vec![
+            Lint {
+                line: 4,
+                kind: LintKind::NeverTrue,
+            },
+            Lint {
+                line: 4,
+                kind: LintKind::UnusedRegister(String::from("b")),
+            },
+        ]),
+        analyze(&program)
+    );
+
+    let program = parse_program::<i128>(
+        "a set 1180591620717411303424\nb inc 1 if a > 18446744073709551616\nc inc 1 if b > 0",
+    ).unwrap();
+    assert_eq!(
+        Ok(vec![Lint {
+            line: 3,
+            kind: LintKind::UnusedRegister(String::from("c")),
+        }]),
+        analyze(&program)
+    );
 }
 
 #[test]
 fn test_from_str() {
-    assert_eq!(Err(utils::Error), Instruction::from_str(""));
+    assert_eq!(Err(utils::Error), Instruction::<i32>::from_str(""));
+    assert_eq!(Err(utils::Error), Instruction::<i32>::from_str("a inc 1 if"));
+    assert_eq!(Err(utils::Error), Instruction::<i32>::from_str("a add 1"));
+    assert_eq!(Err(utils::Error), Instruction::<i32>::from_str("jmp"));
     assert_eq!(
         Ok(Instruction::new(
             "b",
             Operation::Inc,
-            5,
-            Condition::new("a", Compare::HT, 1),
+            Operand::Value(5),
+            Condition::new("a", Compare::HT, Operand::Value(1)),
         )),
         Instruction::from_str("b inc 5 if a > 1")
     );
@@ -202,8 +1012,8 @@ fn test_from_str() {
         Ok(Instruction::new(
             "a",
             Operation::Inc,
-            1,
-            Condition::new("b", Compare::LT, 5),
+            Operand::Value(1),
+            Condition::new("b", Compare::LT, Operand::Value(5)),
         )),
         Instruction::from_str("a inc 1 if b < 5")
     );
@@ -211,8 +1021,8 @@ fn test_from_str() {
         Ok(Instruction::new(
             "c",
             Operation::Dec,
-            -10,
-            Condition::new("a", Compare::HE, 1),
+            Operand::Value(-10),
+            Condition::new("a", Compare::HE, Operand::Value(1)),
         )),
         Instruction::from_str("c dec -10 if a >= 1")
     );
@@ -220,9 +1030,38 @@ fn test_from_str() {
         Ok(Instruction::new(
             "c",
             Operation::Inc,
-            -20,
-            Condition::new("c", Compare::EQ, 10),
+            Operand::Value(-20),
+            Condition::new("c", Compare::EQ, Operand::Value(10)),
         )),
         Instruction::from_str("c inc -20 if c == 10")
     );
+    assert_eq!(
+        Ok(Instruction::<i32>::new(
+            "a",
+            Operation::Mul,
+            Operand::Register(String::from("b")),
+            Condition::new("c", Compare::HT, Operand::Register(String::from("d"))),
+        )),
+        Instruction::from_str("a mul b if c > d")
+    );
+    assert_eq!(
+        Ok(Instruction::Modify(
+            String::from("a"),
+            Operation::Set,
+            Operand::Value(3),
+            None,
+        )),
+        Instruction::from_str("a set 3")
+    );
+    assert_eq!(
+        Ok(Instruction::Jump(
+            String::from("loop"),
+            Some(Condition::new("a", Compare::NE, Operand::Value(0))),
+        )),
+        Instruction::from_str("jmp loop if a != 0")
+    );
+    assert_eq!(
+        Ok(Instruction::<i32>::Label(String::from("loop"))),
+        Instruction::from_str("loop:")
+    );
 }
@@ -1,29 +1,19 @@
-mod aoc1;
-mod aoc2;
-mod aoc3;
-mod aoc4;
-mod aoc5;
-mod aoc6;
-mod aoc7;
-mod aoc8;
-mod aoc9;
-mod aoc10;
-mod aoc11;
-mod aoc12;
-mod aoc13;
-mod aoc14;
-mod aoc15;
-mod aoc16;
-mod aoc17;
-mod aoc18;
-mod aoc19;
-mod aoc20;
-mod utils;
+extern crate aoc20017;
 
+use aoc20017::*;
 use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "knothash" {
+        if !knot_hash_files(&args[2..]) {
+            process::exit(1);
+        }
+        return;
+    }
     if args.len() < 3 {
         println!("Invalid arguments!");
         return;
@@ -274,3 +264,74 @@ fn main() {
         _ => println!("Unknown puzzle!"),
     }
 }
+
+fn knot_hash_files(args: &[String]) -> bool {
+    let check = args.first().is_some_and(|x| x == "--check");
+    let mut files = args.iter().skip(if check { 1 } else { 0 }).collect::<Vec<_>>();
+    let stdin = String::from("-");
+    if files.is_empty() {
+        files.push(&stdin);
+    }
+    let mut success = true;
+    for file in files {
+        let data = match read_input(file) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("{}: {}", file, err);
+                success = false;
+                continue;
+            }
+        };
+        if check {
+            success &= check_knot_hashes(&String::from_utf8_lossy(&data));
+        } else {
+            println!("{}", aoc10::format_checksum(&data, file));
+        }
+    }
+    success
+}
+
+fn check_knot_hashes(checksums: &str) -> bool {
+    let mut failed = 0;
+    let mut malformed = 0;
+    for line in checksums.lines() {
+        let (hash, file) = match aoc10::parse_checksum(line) {
+            Some(checksum) => checksum,
+            None => {
+                malformed += 1;
+                continue;
+            }
+        };
+        match read_input(file) {
+            Ok(data) => {
+                if aoc10::knot_hash_hex(&data) == hash.to_lowercase() {
+                    println!("{}: OK", file);
+                } else {
+                    println!("{}: FAILED", file);
+                    failed += 1;
+                }
+            }
+            Err(_) => {
+                println!("{}: FAILED open or read", file);
+                failed += 1;
+            }
+        }
+    }
+    if malformed > 0 {
+        eprintln!("WARNING: {} line(s) are improperly formatted", malformed);
+    }
+    if failed > 0 {
+        eprintln!("WARNING: {} computed checksum(s) did NOT match", failed);
+    }
+    failed == 0 && malformed == 0
+}
+
+fn read_input(file: &str) -> io::Result<Vec<u8>> {
+    if file == "-" {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        fs::read(file)
+    }
+}
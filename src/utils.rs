@@ -4,7 +4,7 @@ use std::io::Read;
 use std::num::ParseIntError;
 use std::str::FromStr;
 
-pub fn merge_args(args: &Vec<String>, first: usize, separator: &str) -> String {
+pub fn merge_args(args: &[String], first: usize, separator: &str) -> String {
     if first >= args.len() {
         return String::new();
     }
@@ -17,7 +17,7 @@ pub fn merge_args(args: &Vec<String>, first: usize, separator: &str) -> String {
 pub fn parse_numbers<T: FromStr>(digits: &str, delimiter: char) -> Option<Vec<T>> {
     let mut row = vec![];
     let digits = digits.trim();
-    if digits.len() > 0 {
+    if !digits.is_empty() {
         for item in digits.split(delimiter) {
             match item.trim().parse::<T>() {
                 Ok(x) => row.push(x),
@@ -66,12 +66,12 @@ fn test_parse_numbers() {
 
 #[test]
 fn test_merge_args() {
-    assert_eq!("", merge_args(&vec![], 0, " "));
-    assert_eq!("x", merge_args(&vec![String::from("x")], 0, " "));
+    assert_eq!("", merge_args(&[], 0, " "));
+    assert_eq!("x", merge_args(&[String::from("x")], 0, " "));
     assert_eq!(
         "y z",
         merge_args(
-            &vec![String::from("x"), String::from("y"), String::from("z")],
+            &[String::from("x"), String::from("y"), String::from("z")],
             1,
             " ",
         )